@@ -0,0 +1,87 @@
+//! Compares the search throughput of SearchTree and ArenaSearchTree.
+//! Run with `cargo run --release --example throughput`.
+
+use std::fmt;
+use std::time::Instant;
+use mocats::{ArenaSearchTree, GameAction, GameState, Player, SearchTree, UctPolicy};
+use mocats::tic_tac_toe::TicTacToePosition;
+
+const ITERATIONS: usize = 1_000_000;
+
+/// A two-player game on a wide and deep tree, whose states are scored by hashing the path to them.
+/// Rollouts are cut short immediately, so that the cost of an iteration is dominated by the tree itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PathState {
+    hash: u64,
+    depth: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathPlayer { First, Second }
+impl Player for PathPlayer {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PathAction(u64);
+impl GameAction for PathAction {}
+impl fmt::Display for PathAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl GameState<PathAction, PathPlayer> for PathState {
+    fn get_actions(&self) -> Vec<PathAction> {
+        match self.depth < 40 {
+            true => (0..8).map(PathAction).collect(),
+            false => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &PathAction) {
+        self.hash = (self.hash ^ action.0).wrapping_mul(0x100000001b3);
+        self.depth += 1;
+    }
+
+    fn get_turn(&self) -> PathPlayer {
+        match self.depth % 2 {
+            0 => PathPlayer::First,
+            _ => PathPlayer::Second
+        }
+    }
+
+    fn get_reward_for_player(&self, player: PathPlayer) -> f32 {
+        let value = (self.hash >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0;
+        match player {
+            PathPlayer::First => value,
+            PathPlayer::Second => -value
+        }
+    }
+}
+
+fn main() {
+    println!("Tic-tac-toe:");
+    let start = Instant::now();
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_seed(0);
+    mcts.run(ITERATIONS);
+    report("SearchTree", start, mcts.get_node_count());
+    let start = Instant::now();
+    let mut mcts = ArenaSearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_seed(0);
+    mcts.run(ITERATIONS);
+    report("ArenaSearchTree", start, mcts.get_node_count());
+
+    println!("Deep tree without rollouts:");
+    let game = PathState { hash: 0xcbf29ce484222325, depth: 0 };
+    let start = Instant::now();
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_seed(0).with_max_rollout_depth(0);
+    mcts.run(ITERATIONS);
+    report("SearchTree", start, mcts.get_node_count());
+    let start = Instant::now();
+    let mut mcts = ArenaSearchTree::new(game, UctPolicy::new(2.)).with_seed(0).with_max_rollout_depth(0);
+    mcts.run(ITERATIONS);
+    report("ArenaSearchTree", start, mcts.get_node_count());
+}
+
+fn report(name: &str, start: Instant, node_count: u32) {
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("    {:<16} {:>10.0} iterations/s ({} iterations in {:.2}s, {} nodes)", name, ITERATIONS as f64 / elapsed, ITERATIONS, elapsed, node_count);
+}
@@ -0,0 +1,319 @@
+//! Contains the ArenaSearchTree struct, a search tree whose nodes are stored contiguously in an arena.
+
+use fastrand::Rng;
+
+use crate::game::{GameAction, GameState, Player};
+use crate::prior_provider::{PriorProvider, UniformPriorProvider};
+use crate::rollout_policy::{simulate, RolloutPolicy, UniformRolloutPolicy};
+use crate::search_config::{ExpansionStrategy, SearchConfig};
+use crate::search_limit::{LimitTracker, SearchLimit};
+use crate::search_node::NodeState;
+use crate::tree_policy::ArenaTreePolicy;
+
+/// Represents a node in an ArenaSearchTree. The children of a node are stored next to each other in the arena.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArenaNode<A> where A: GameAction {
+    /// The action that this node represents. Only None for the root node.
+    pub action: Option<A>,
+    /// The ID of the first child of this node in the arena. Only meaningful if the node has children.
+    pub first_child: u32,
+    /// The number of children of this node.
+    pub child_count: u32,
+    /// The state of this node.
+    pub state: NodeState,
+    /// The number of times this node has been visited.
+    pub visits: u32,
+    /// The total value of this node as a result of rollouts.
+    pub total_value: f32,
+    /// The prior probability of this node's action, as given by the prior provider when the node was added.
+    /// For outcomes of random events, this is the probability of the outcome instead.
+    pub prior: f32
+}
+
+impl<A> ArenaNode<A> where A: GameAction {
+    /// Constructs a new arena node with the given action and prior.
+    pub fn new(action: Option<A>, prior: f32) -> ArenaNode<A> {
+        ArenaNode {
+            action,
+            first_child: 0,
+            child_count: 0,
+            state: NodeState::ExpandableLeaf,
+            visits: 0,
+            total_value: 0.0,
+            prior
+        }
+    }
+}
+
+/// Represents a MCTS search tree whose nodes are stored in a single contiguous arena and addressed by `u32` IDs,
+/// so that expanding a node takes no allocation of its own, and selection and backpropagation run without recursion.
+/// All the children of a node are added when it is first expanded, in a random order unless expanding with
+/// `ExpansionStrategy::All`, so node counts include children that haven't been visited yet.
+/// Supports paranoid search with chance nodes, rollout policies, priors and action scores, and plays the most visited action.
+/// Unlike `SearchTree`, it doesn't support MCTS-Solver, score bounds, max^n search, information set search, transpositions,
+/// progressive widening, node and memory limits, parallel search, other final selection strategies, search reports,
+/// printing, exporting or serializing the tree.
+pub struct ArenaSearchTree<S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: ArenaTreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl> = UniformRolloutPolicy, Pr: PriorProvider<S, A, Pl> = UniformPriorProvider> {
+    /// The nodes of the search tree. The root node has ID 0.
+    nodes: Vec<ArenaNode<A>>,
+    /// The initial game state.
+    root_game_state: S,
+    /// The player whose turn it was at the root node (initial game position).
+    root_player: Pl,
+    /// The tree policy to use.
+    policy: Po,
+    /// The rollout policy to use.
+    rollout_policy: Ro,
+    /// The prior provider to use.
+    prior_provider: Pr,
+    /// The settings used while running iterations.
+    config: SearchConfig,
+    /// The random number generator used for expansion, rollouts and any other random choice.
+    rng: Rng,
+    /// The IDs of the nodes visited by the current iteration, reused between iterations.
+    path: Vec<u32>
+}
+
+impl<S, A, Pl, Po> ArenaSearchTree<S, A, Pl, Po> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: ArenaTreePolicy<A, Pl> {
+    /// Constructs a new arena search tree from the given game state, using uniformly random rollouts and uniform priors.
+    pub fn new(game: S, tree_policy: Po) -> ArenaSearchTree<S, A, Pl, Po> {
+        ArenaSearchTree {
+            nodes: vec![ArenaNode::new(None, 1.0)],
+            root_player: game.get_turn(),
+            root_game_state: game,
+            policy: tree_policy,
+            rollout_policy: UniformRolloutPolicy,
+            prior_provider: UniformPriorProvider,
+            config: SearchConfig::default(),
+            rng: Rng::new(),
+            path: Vec::new()
+        }
+    }
+}
+
+impl<S, A, Pl, Po, Ro, Pr> ArenaSearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: ArenaTreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+    /// Replaces the rollout policy used to play out the game from newly expanded nodes.
+    pub fn with_rollout_policy<Ro2>(self, rollout_policy: Ro2) -> ArenaSearchTree<S, A, Pl, Po, Ro2, Pr> where Ro2: RolloutPolicy<S, A, Pl> {
+        ArenaSearchTree {
+            nodes: self.nodes,
+            root_game_state: self.root_game_state,
+            root_player: self.root_player,
+            policy: self.policy,
+            rollout_policy,
+            prior_provider: self.prior_provider,
+            config: self.config,
+            rng: self.rng,
+            path: self.path
+        }
+    }
+
+    /// Replaces the prior provider used to set the priors of newly expanded nodes, as used by `PuctPolicy`.
+    pub fn with_prior_provider<Pr2>(self, prior_provider: Pr2) -> ArenaSearchTree<S, A, Pl, Po, Ro, Pr2> where Pr2: PriorProvider<S, A, Pl> {
+        ArenaSearchTree {
+            nodes: self.nodes,
+            root_game_state: self.root_game_state,
+            root_player: self.root_player,
+            policy: self.policy,
+            rollout_policy: self.rollout_policy,
+            prior_provider,
+            config: self.config,
+            rng: self.rng,
+            path: self.path
+        }
+    }
+
+    /// Seeds the random number generator, so that searches with identical seeds produce identical trees.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::with_seed(seed);
+        self
+    }
+
+    /// Replaces the settings used while running iterations.
+    /// Only the maximum rollout depth and the expansion strategy apply to arena search trees; the other settings are ignored.
+    pub fn with_config(mut self, config: SearchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Limits rollouts to the given number of actions, after which the game is scored with `GameState::evaluate`.
+    pub fn with_max_rollout_depth(mut self, max_rollout_depth: usize) -> Self {
+        self.config.max_rollout_depth = Some(max_rollout_depth);
+        self
+    }
+
+    /// Sets how leaf nodes are expanded. Defaults to `ExpansionStrategy::Incremental`.
+    pub fn with_expansion_strategy(mut self, expansion: ExpansionStrategy) -> Self {
+        self.config.expansion = expansion;
+        self
+    }
+
+    /// Reserves room in the arena for at least the given number of nodes, so that it isn't reallocated while searching.
+    pub fn with_capacity(mut self, nodes: usize) -> Self {
+        self.nodes.reserve(nodes.saturating_sub(self.nodes.len()));
+        self
+    }
+
+    /// Runs the MCTS algorithm for the given number of iterations.
+    pub fn run(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            self.run_iteration();
+        }
+    }
+
+    /// Runs the MCTS algorithm until any of the given limits is reached.
+    /// Returns the number of iterations that were run.
+    pub fn run_until(&mut self, limit: SearchLimit) -> usize {
+        let mut tracker = LimitTracker::start(limit);
+        while tracker.next_iteration(self.get_node_count()) {
+            self.run_iteration();
+        }
+        tracker.get_iterations()
+    }
+
+    /// Runs a single iteration of the MCTS algorithm from the root game state.
+    /// Descends the tree in a loop, recording the visited nodes, then backs the reward up along them.
+    fn run_iteration(&mut self) {
+        let mut game = self.root_game_state.clone();
+        let mut path = std::mem::take(&mut self.path);
+        path.clear();
+        let mut id = 0;
+        path.push(id);
+        let reward = loop {
+            let node = self.nodes[id as usize];
+            match node.state {
+                NodeState::TerminalLeaf => break game.get_reward_for_player(self.root_player),
+                NodeState::ExpandableLeaf => {
+                    if !self.expand(id, &game) {
+                        break game.get_reward_for_player(self.root_player);
+                    }
+                    if self.config.expansion == ExpansionStrategy::All && self.nodes[id as usize].state != NodeState::Chance {
+                        break simulate(&mut game, &self.rollout_policy, &self.config, self.root_player, &mut self.rng);
+                    }
+                },
+                NodeState::Expanded | NodeState::Chance => {
+                    let children = &self.nodes[node.first_child as usize..(node.first_child + node.child_count) as usize];
+                    let index = match node.state {
+                        NodeState::Chance => sample_child(children, &mut self.rng),
+                        _ => self.policy.select_arena_child(node.visits, children, game.get_turn() == self.root_player)
+                    };
+                    let child = children[index];
+                    id = node.first_child + index as u32;
+                    path.push(id);
+                    game.apply_action(&child.action.expect("Expected child node to have action"));
+                    if child.visits == 0 && self.config.expansion == ExpansionStrategy::Incremental {
+                        break simulate(&mut game, &self.rollout_policy, &self.config, self.root_player, &mut self.rng);
+                    }
+                }
+            }
+        };
+        for &id in &path {
+            let node = &mut self.nodes[id as usize];
+            node.visits += 1;
+            node.total_value += reward;
+        }
+        self.path = path;
+    }
+
+    /// Adds the children of the given leaf node at the end of the arena: one per chance outcome if the next transition
+    /// is a random event, and one per legal action otherwise. When expanding incrementally, the actions are ordered
+    /// by `GameState::get_action_scores` from highest to lowest score if the game state scores them, and shuffled otherwise.
+    /// If there are no legal actions, marks the node as a TerminalLeaf and returns false.
+    fn expand(&mut self, id: u32, game: &S) -> bool {
+        let first_child = self.nodes.len();
+        let state = match game.get_chance_outcomes() {
+            Some(outcomes) => {
+                let total_weight: f32 = outcomes.iter().map(|(_, weight)| weight).sum();
+                self.nodes.extend(outcomes.iter().map(|&(outcome, weight)| ArenaNode::new(Some(outcome), weight / total_weight)));
+                NodeState::Chance
+            },
+            None => {
+                let ordered_actions = match self.config.expansion {
+                    ExpansionStrategy::Incremental => game.get_action_scores().map(|mut scores| {
+                        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+                        scores.into_iter().map(|(action, _)| action).collect::<Vec<A>>()
+                    }),
+                    ExpansionStrategy::All => None
+                };
+                let is_ordered = ordered_actions.is_some();
+                let actions = ordered_actions.unwrap_or_else(|| game.get_actions());
+                if actions.is_empty() {
+                    self.nodes[id as usize].state = NodeState::TerminalLeaf;
+                    return false;
+                }
+                let priors = self.prior_provider.get_priors(game, &actions);
+                self.nodes.extend(actions.iter().zip(&priors).map(|(&action, &prior)| ArenaNode::new(Some(action), prior)));
+                if self.config.expansion == ExpansionStrategy::Incremental && !is_ordered {
+                    self.rng.shuffle(&mut self.nodes[first_child..]);
+                }
+                NodeState::Expanded
+            }
+        };
+        assert!(self.nodes.len() <= u32::MAX as usize, "Expected arena to have at most u32::MAX nodes");
+        let child_count = (self.nodes.len() - first_child) as u32;
+        let node = &mut self.nodes[id as usize];
+        node.first_child = first_child as u32;
+        node.child_count = child_count;
+        node.state = state;
+        true
+    }
+
+    /// Advances the root of the search tree by playing the given action, keeping the statistics of the subtree below it.
+    /// The kept nodes are copied to the front of the arena, which keeps its capacity.
+    pub fn advance(&mut self, action: &A) {
+        self.root_game_state.apply_action(action);
+        let root = self.nodes[0];
+        let children = &self.nodes[root.first_child as usize..(root.first_child + root.child_count) as usize];
+        let mut nodes = Vec::with_capacity(self.nodes.capacity());
+        match children.iter().position(|child| child.action == Some(*action)) {
+            Some(index) => {
+                let mut new_root = children[index];
+                new_root.action = None;
+                nodes.push(new_root);
+                let mut id = 0;
+                while id < nodes.len() {
+                    let node = nodes[id];
+                    if node.child_count > 0 {
+                        nodes[id].first_child = nodes.len() as u32;
+                        nodes.extend_from_slice(&self.nodes[node.first_child as usize..(node.first_child + node.child_count) as usize]);
+                    }
+                    id += 1;
+                }
+            },
+            None => {
+                nodes.push(ArenaNode::new(None, 1.0));
+                self.root_player = self.root_game_state.get_turn();
+            }
+        }
+        self.nodes = nodes;
+    }
+
+    /// Returns the number of nodes in the search tree, including children that haven't been visited yet.
+    pub fn get_node_count(&self) -> u32 {
+        self.nodes.len() as u32
+    }
+
+    /// Returns the nodes of the search tree. The root node has ID 0, and the children of a node are contiguous.
+    pub fn get_nodes(&self) -> &[ArenaNode<A>] {
+        &self.nodes
+    }
+
+    /// Returns the best action according to the MCTS algorithm.
+    pub fn get_best_action(&self) -> Option<A> {
+        let root = self.nodes[0];
+        self.nodes[root.first_child as usize..(root.first_child + root.child_count) as usize].iter()
+            .reduce(|a, b| if a.visits > b.visits { a } else { b })
+            .map(|n| n.action.expect("Expected node to have action"))
+    }
+}
+
+/// Samples one of the given outcomes of a random event by probability, returning its index.
+fn sample_child<A>(children: &[ArenaNode<A>], rng: &mut Rng) -> usize where A: GameAction {
+    let mut threshold = rng.f32();
+    for (index, child) in children.iter().enumerate() {
+        if threshold < child.prior {
+            return index;
+        }
+        threshold -= child.prior;
+    }
+    children.len() - 1
+}
@@ -0,0 +1,67 @@
+//! Contains the FinalSelection enum, which defines how the action to play is chosen once the search is done.
+
+use fastrand::Rng;
+use crate::game::{sample_chance_outcome, GameAction, Player};
+use crate::search_node::SearchNode;
+
+/// Represents a strategy for choosing the action to play among the children of the root node.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FinalSelection {
+    /// Chooses the most visited child.
+    #[default]
+    MaxVisits,
+    /// Chooses the child with the highest mean value for the player to move.
+    MaxValue,
+    /// Chooses the child with both the most visits and the highest mean value, or the most visited child if there is none.
+    RobustMax,
+    /// Chooses the child with the highest lower confidence bound on its mean value,
+    /// i.e. the mean value minus the given confidence over the square root of the visits.
+    SecureChild {
+        /// The weight of the uncertainty of the mean value.
+        confidence: f32
+    },
+    /// Samples a child with a probability proportional to its visits raised to the power of 1 / temperature.
+    /// Higher temperatures give more diverse choices, as used in self-play, and lower ones approach MaxVisits.
+    Sample {
+        /// The temperature of the sampling. Must be positive.
+        temperature: f32
+    }
+}
+
+impl FinalSelection {
+    /// Chooses one of the given children of the root node, whose value is multiplied by the given sign to get the value for the player to move.
+    /// Ties are broken in favor of the last child. Returns None if there are no children.
+    pub(crate) fn select<'a, A, Pl>(&self, children: Vec<&'a SearchNode<A, Pl>>, sign: f32, rng: &mut Rng) -> Option<&'a SearchNode<A, Pl>> where A: GameAction, Pl: Player {
+        let mean_value = |child: &SearchNode<A, Pl>| match child.visits {
+            0 => f32::NEG_INFINITY,
+            visits => sign*child.total_value / visits as f32
+        };
+        let max_by = |score: &dyn Fn(&SearchNode<A, Pl>) -> f32| children.iter().copied().reduce(|a, b| if score(a) > score(b) { a } else { b });
+        match *self {
+            FinalSelection::MaxVisits => max_by(&|child| child.visits as f32),
+            FinalSelection::MaxValue => max_by(&mean_value),
+            FinalSelection::RobustMax => {
+                let most_visited = max_by(&|child| child.visits as f32);
+                let highest_value = max_by(&mean_value);
+                match (most_visited, highest_value) {
+                    (Some(most_visited), Some(highest_value)) if most_visited.visits == highest_value.visits => Some(highest_value),
+                    (Some(most_visited), Some(highest_value)) if mean_value(most_visited) == mean_value(highest_value) => Some(most_visited),
+                    _ => most_visited
+                }
+            },
+            FinalSelection::SecureChild { confidence } => max_by(&|child| mean_value(child) - confidence / (child.visits as f32).sqrt()),
+            FinalSelection::Sample { temperature } => {
+                assert!(temperature > 0.0, "Temperature must be positive");
+                let max_visits = children.iter().map(|child| child.visits).max()?;
+                if max_visits == 0 {
+                    return max_by(&|child| child.visits as f32);
+                }
+                let weights: Vec<(usize, f32)> = children.iter().enumerate()
+                    .map(|(index, child)| (index, (child.visits as f32 / max_visits as f32).powf(1.0 / temperature)))
+                    .collect();
+                Some(children[sample_chance_outcome(&weights, rng).0])
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //! Contains the traits that define a game.
 
 use std::fmt::{Debug, Display};
-
+use fastrand::Rng;
 
 /// Represents a game state.
 pub trait GameState<A: GameAction, P: Player> : Clone {
@@ -13,6 +13,34 @@ pub trait GameState<A: GameAction, P: Player> : Clone {
     fn get_turn(&self) -> P;
     /// Returns whether the game is over.
     fn get_reward_for_player(&self, player: P) -> f32;
+    /// Returns a static evaluation of this state for the given player, used when a rollout is cut short.
+    /// Should be on the same scale as get_reward_for_player, which is what the default implementation returns.
+    fn evaluate(&self, player: P) -> f32 {
+        self.get_reward_for_player(player)
+    }
+    /// Returns the reward for each of the given players, in the same order. Used by max^n search.
+    /// The default implementation calls get_reward_for_player for every player.
+    fn get_rewards(&self, players: &[P]) -> Vec<f32> {
+        players.iter().map(|&player| self.get_reward_for_player(player)).collect()
+    }
+    /// Returns the possible outcomes of a random event, with their probabilities, if the next transition is one.
+    /// The search then samples an outcome by probability, instead of choosing one of get_actions.
+    /// The default implementation returns None, as in deterministic games.
+    fn get_chance_outcomes(&self) -> Option<Vec<(A, f32)>> {
+        None
+    }
+    /// Returns the legal actions with heuristic scores, higher being more promising, if this state can order its actions.
+    /// Incremental expansion then adds the untried action with the highest score, instead of a random one.
+    /// Should return the same actions as get_actions. The default implementation returns None.
+    fn get_action_scores(&self) -> Option<Vec<(A, f32)>> {
+        None
+    }
+    /// Returns a full game state sampled from the information set of the given player,
+    /// i.e. consistent with everything that player can observe. Used by information set search.
+    /// The default implementation returns a copy of this state, as in games with perfect information.
+    fn determinize(&self, _observer: P, _rng: &mut Rng) -> Self {
+        self.clone()
+    }
 }
 
 /// Represents a legal game action that can be applied to some GameState.
@@ -20,3 +48,34 @@ pub trait GameAction: Debug+PartialEq+Copy+Display {}
 
 /// Represents a player in a game. Should be an enum.
 pub trait Player: Debug+Eq+Copy {}
+
+/// Chooses which untried action to expand: the one with the highest score from `GameState::get_action_scores`
+/// (the first one on ties) if the game state scores its actions, and a random one of the given candidates otherwise.
+pub(crate) fn choose_untried_action<S, A, P>(game: &S, candidate_actions: &[A], is_untried: impl Fn(&A) -> bool, rng: &mut Rng) -> A where S: GameState<A, P>, A: GameAction, P: Player {
+    let mut best_scored: Option<(A, f32)> = None;
+    for (action, score) in game.get_action_scores().unwrap_or_default() {
+        if is_untried(&action) && best_scored.is_none_or(|(_, best_score)| score > best_score) {
+            best_scored = Some((action, score));
+        }
+    }
+    match best_scored {
+        Some((action, _)) => action,
+        None => candidate_actions[rng.usize(0..candidate_actions.len())]
+    }
+}
+
+/// Samples one of the given outcomes of a random event, with a probability proportional to its weight.
+/// Returns the outcome along with its normalized probability.
+pub fn sample_chance_outcome<A: Copy>(outcomes: &[(A, f32)], rng: &mut Rng) -> (A, f32) {
+    assert!(!outcomes.is_empty(), "Expected at least one chance outcome");
+    let total_weight: f32 = outcomes.iter().map(|(_, weight)| weight).sum();
+    let mut threshold = rng.f32() * total_weight;
+    for &(outcome, weight) in outcomes {
+        if threshold < weight {
+            return (outcome, weight / total_weight);
+        }
+        threshold -= weight;
+    }
+    let (outcome, weight) = outcomes[outcomes.len() - 1];
+    (outcome, weight / total_weight)
+}
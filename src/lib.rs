@@ -5,14 +5,30 @@
 //!
 //! A fast, easy-to-use, generalized Monte Carlo Tree Search library.
 //! Works for any game, any number of players, and any tree policy (UctPolicy included as a default).
-//! As of the current version, the search is single-threaded.
+//! The search runs on a single thread by default, or across multiple threads with `run_parallel` (independent trees)
+//! or `run_tree_parallel` (one shared tree with virtual loss).
 //!
 //! ## Features
 //! - Fast and efficient Monte Carlo Tree Search implementation
 //! - Easy-to-use API
-//! - Customizable number of players (uses paranoid approach for more than 2 players)
+//! - Customizable number of players (uses paranoid approach for more than 2 players by default, or max^n with `SearchTree::with_max_n`)
 //! - Customizable tree policies
-//! - Nicely formatted display output for debugging
+//! - Stochastic games, through chance nodes (`GameState::get_chance_outcomes`)
+//! - Hidden-information games, through information set search (`SearchTree::with_information_set_search`)
+//! - Transposition tables for games whose `GameState` implements `Hash + Eq` (`SearchTree::with_transpositions`)
+//! - MCTS-Solver, which proves wins, losses and draws and stops searching proven lines (`SearchTree::with_solver`)
+//! - Score-bounded MCTS for games with multi-valued outcomes (`SearchTree::with_score_bounds`)
+//! - Progressive widening and double progressive widening for games with huge numbers of actions or chance outcomes (`SearchTree::with_progressive_widening`, `SearchTree::with_double_progressive_widening`)
+//! - Heuristic action ordering, so that incremental expansion tries the most promising actions first (`GameState::get_action_scores`)
+//! - Node and memory limits that prune the least visited subtrees or stop expanding the tree (`SearchTree::with_node_limit`, `SearchTree::with_memory_limit`)
+//! - Root-parallel and tree-parallel multithreaded search
+//! - Arena-allocated search trees with iterative selection and backpropagation for large searches (`ArenaSearchTree`, see `examples/throughput.rs`)
+//! - Reproducible searches with a seedable random number generator (`SearchTree::with_seed`)
+//! - Configurable final move selection, including sampling by visit count for self-play (`SearchTree::with_final_selection`)
+//! - Structured search reports with per-action statistics and the principal variation (`SearchTree::get_report`)
+//! - Nicely formatted display output for debugging, limited by depth, visits or top children with `SearchTree::display`
+//! - Graphviz DOT and JSON export of the search tree, with depth and visit filters (`SearchTree::to_dot`, `SearchTree::to_json`)
+//! - Saving and loading search trees with the optional `serde` feature
 //!
 //! ## Usage
 //!
@@ -29,6 +45,15 @@
 //! mocats = "0.2.1"
 //! ```
 //!
+//! To save and load search trees (e.g. as opening books), enable the `serde` feature.
+//! `SearchTree` then implements `Serialize` and `Deserialize` when the game state, action, player and policy types do,
+//! in a format tagged with `SEARCH_TREE_FORMAT_VERSION`:
+//!
+//! ```toml
+//! [dependencies]
+//! mocats = { version = "0.3.0", features = ["serde"] }
+//! ```
+//!
 //! ### Defining a game
 //!
 //! To use `mocats`, you must define a game and a tree policy. A game is defined by three traits:
@@ -43,9 +68,21 @@
 //!
 //! The `UctPolicy` struct is included as a default tree policy.
 //!
+//! The `PuctPolicy` struct weights exploration by each action's prior probability, as in AlphaZero.
+//! Priors are uniform by default; to supply them from a heuristic or a network, implement `PriorProvider`
+//! and pass it to `SearchTree::with_prior_provider`.
+//!
+//! Leaf nodes are expanded one random child at a time by default. `SearchTree::with_expansion_strategy(ExpansionStrategy::All)`
+//! adds all children of a leaf at once instead, calling the prior provider and the rollout policy once per node.
+//!
+//! Rollouts (playouts from newly expanded nodes) are uniformly random by default.
+//! To use heuristic playouts instead, implement `RolloutPolicy` and pass it to `SearchTree::with_rollout_policy`.
+//! For long games, `SearchTree::with_max_rollout_depth` cuts rollouts short and scores them with `GameState::evaluate`.
+//!
 //! ### Running the search
 //!
 //! To run the search, create a `SearchTree` struct with the game and tree policy, then call `run` on it.
+//! To search within a wall-clock or node budget instead, call `run_until` with a `SearchLimit`.
 //!
 //! ```rust
 //! use mocats::{tic_tac_toe, UctPolicy};
@@ -150,16 +187,41 @@
 //!
 //! This project is licensed under the MIT License. See the [LICENSE file](./LICENSE) for details.
 
+mod arena_search_tree;
+mod final_selection;
 mod game;
+mod node_limit;
+mod prior_provider;
+mod rollout_policy;
+mod search_config;
+mod search_limit;
 mod search_node;
+mod search_report;
 mod search_tree;
+mod shared_search_node;
+mod transposition_table;
+mod tree_export;
+mod tree_format;
 mod tree_policy;
 pub mod tic_tac_toe;
 
 #[cfg(test)]
 mod tests;
 
+pub use arena_search_tree::*;
+pub use final_selection::*;
 pub use game::*;
+pub use node_limit::*;
+pub use prior_provider::*;
+pub use rollout_policy::*;
+pub use search_config::*;
+pub use search_limit::*;
 pub use search_node::*;
+pub use search_report::*;
 pub use search_tree::*;
-pub use tree_policy::*;
\ No newline at end of file
+pub use shared_search_node::*;
+pub use transposition_table::*;
+pub use tree_export::*;
+pub use tree_format::*;
+pub use tree_policy::*;
+pub use fastrand::Rng;
\ No newline at end of file
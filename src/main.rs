@@ -53,10 +53,11 @@ fn main() {
         "n"|"N" => false,
         _ => panic!("Invalid input")
     };
+    let mut mcts = SearchTree::<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>::new(game, UctPolicy::new(2.));
     if !user_turn {
-        play_best_move(&mut game);
+        play_best_move(&mut game, &mut mcts);
     }
-    assert!(game.get_moves().len() > 0, "Game is already over");
+    assert!(!game.get_moves().is_empty(), "Game is already over");
     println!("Print moves as tl, tm, tr, ml, mm, mr, bl, bm, br");
     println!();
     loop {
@@ -84,12 +85,13 @@ fn main() {
             panic!("Invalid move");
         }
         game.make_move(m);
+        mcts.advance(&TicTacToeMove { pos: m });
         println!("Current position:");
         println!("{}", game);
         if game.get_moves().is_empty() {
             break;
         }
-        play_best_move(&mut game);
+        play_best_move(&mut game, &mut mcts);
     }
     println!("Final position:");
     println!("{}", game);
@@ -100,10 +102,10 @@ fn main() {
     }
 }
 
-fn play_best_move(position: &mut TicTacToePosition) {
-    let mut mcts = SearchTree::<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>::new(*position, UctPolicy::new(2.));
+fn play_best_move(position: &mut TicTacToePosition, mcts: &mut SearchTree<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>) {
     mcts.run(2000);
-    let best = mcts.get_best_action().unwrap().pos;
-    position.make_move(best);
+    let best = mcts.get_best_action().unwrap();
+    position.make_move(best.pos);
+    mcts.advance(&best);
     println!("Bot played a move!\n");
 }
\ No newline at end of file
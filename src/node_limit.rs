@@ -0,0 +1,66 @@
+//! Contains the NodeLimit struct, which caps the size of a search tree, and the statistics of the nodes it reclaimed.
+
+/// The share of the node limit that `NodeLimit::prune` prunes the tree back to.
+const DEFAULT_PRUNE_TARGET: f32 = 0.75;
+
+/// Represents a ceiling on the number of nodes in a search tree, and what the tree does when it reaches it.
+/// Expanding all children at once may take the tree past the ceiling by the children of one node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeLimit {
+    /// The maximum number of nodes in the search tree.
+    pub max_nodes: u32,
+    /// What the search tree does when it reaches the maximum number of nodes.
+    pub strategy: NodeLimitStrategy
+}
+
+/// Represents what a search tree does when it reaches its node limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeLimitStrategy {
+    /// Prunes the subtrees of the least visited nodes until the tree has at most the given number of nodes,
+    /// then keeps searching. Nodes that lost children are expanded again when they are next visited.
+    Prune {
+        /// The number of nodes to prune the tree back to. Must be positive and below the maximum number of nodes.
+        target_nodes: u32
+    },
+    /// Stops adding nodes, and keeps sampling the existing leaves with rollouts until the tree shrinks, e.g. by advancing.
+    StopExpanding
+}
+
+impl NodeLimit {
+    /// Constructs a limit that prunes the tree back to three quarters of the given number of nodes whenever it reaches it.
+    pub fn prune(max_nodes: u32) -> Self {
+        assert!(max_nodes > 1, "Node limit must be at least 2 to prune the tree");
+        Self::prune_to(max_nodes, ((max_nodes as f32 * DEFAULT_PRUNE_TARGET) as u32).max(1))
+    }
+
+    /// Constructs a limit that prunes the tree back to the given target number of nodes whenever it reaches the given maximum.
+    pub fn prune_to(max_nodes: u32, target_nodes: u32) -> Self {
+        assert!(target_nodes > 0 && target_nodes < max_nodes, "Prune target must be positive and below the node limit");
+        NodeLimit {
+            max_nodes,
+            strategy: NodeLimitStrategy::Prune { target_nodes }
+        }
+    }
+
+    /// Constructs a limit that stops expanding the tree once it has the given number of nodes.
+    pub fn stop_expanding(max_nodes: u32) -> Self {
+        assert!(max_nodes > 0, "Node limit must be positive");
+        NodeLimit {
+            max_nodes,
+            strategy: NodeLimitStrategy::StopExpanding
+        }
+    }
+}
+
+/// Represents the statistics of a search tree's node limit, as returned by `SearchTree::get_node_limit_stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeLimitStats {
+    /// The number of times the tree has been pruned.
+    pub prunes: u32,
+    /// The total number of nodes removed by pruning.
+    pub reclaimed_nodes: u64,
+    /// Whether the tree has stopped expanding because it is at its node limit.
+    pub is_expansion_stopped: bool
+}
@@ -0,0 +1,21 @@
+//! Contains the trait that defines a prior provider, as well as a preset implementation (UniformPriorProvider).
+
+use crate::game::{GameAction, GameState, Player};
+
+/// A trait that defines a prior provider, which supplies the prior probability P(s, a) of actions
+/// when nodes are expanded, such as the policy output of a neural network or a hand-crafted heuristic.
+pub trait PriorProvider<S, A, Pl>: Sized where S: GameState<A, Pl>, A: GameAction, Pl: Player {
+    /// Returns the prior probability of each of the given legal actions, in the same order.
+    fn get_priors(&self, game: &S, actions: &[A]) -> Vec<f32>;
+}
+
+/// A preset implementation of a prior provider, giving every legal action the same prior.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UniformPriorProvider;
+
+impl<S, A, Pl> PriorProvider<S, A, Pl> for UniformPriorProvider where S: GameState<A, Pl>, A: GameAction, Pl: Player {
+    fn get_priors(&self, _game: &S, actions: &[A]) -> Vec<f32> {
+        vec![1.0 / actions.len() as f32; actions.len()]
+    }
+}
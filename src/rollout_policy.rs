@@ -0,0 +1,68 @@
+//! Contains the trait that defines a rollout policy, as well as a preset implementation (UniformRolloutPolicy).
+
+use fastrand::Rng;
+use crate::game::{sample_chance_outcome, GameAction, GameState, Player};
+use crate::search_config::SearchConfig;
+
+/// A trait that defines a rollout (default) policy, used to play out the game from a newly expanded node.
+pub trait RolloutPolicy<S, A, Pl>: Sized where S: GameState<A, Pl>, A: GameAction, Pl: Player {
+    /// Selects the action to play from the given legal actions, which are never empty.
+    /// Any randomness should be drawn from the given random number generator, so that seeded searches are reproducible.
+    fn select_action(&self, game: &S, actions: &[A], rng: &mut Rng) -> A;
+
+    /// Plays the game out until it is over, or until the given maximum number of actions has been played.
+    /// Returns whether the game is over, as opposed to the rollout having been cut short.
+    /// The default implementation repeatedly applies the action chosen by `select_action`,
+    /// or an outcome sampled by probability when the next transition is a random event.
+    fn rollout(&self, game: &mut S, max_depth: Option<usize>, rng: &mut Rng) -> bool {
+        let mut depth = 0;
+        loop {
+            let outcomes = game.get_chance_outcomes();
+            let available = match outcomes {
+                Some(_) => Vec::new(),
+                None => game.get_actions()
+            };
+            if outcomes.is_none() && available.is_empty() {
+                return true;
+            }
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                return false;
+            }
+            let action = match outcomes {
+                Some(outcomes) => sample_chance_outcome(&outcomes, rng).0,
+                None => self.select_action(game, &available, rng)
+            };
+            game.apply_action(&action);
+            depth += 1;
+        }
+    }
+}
+
+/// A preset implementation of a rollout policy, choosing uniformly random legal actions.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UniformRolloutPolicy;
+
+impl<S, A, Pl> RolloutPolicy<S, A, Pl> for UniformRolloutPolicy where S: GameState<A, Pl>, A: GameAction, Pl: Player {
+    fn select_action(&self, _game: &S, actions: &[A], rng: &mut Rng) -> A {
+        actions[rng.usize(0..actions.len())]
+    }
+}
+
+/// Plays out the game from the given state with the given rollout policy,
+/// and returns the reward (or static evaluation, if the rollout was cut short) for the given player.
+pub(crate) fn simulate<S, A, Pl, Ro>(game: &mut S, rollout_policy: &Ro, config: &SearchConfig, player: Pl, rng: &mut Rng) -> f32 where S: GameState<A, Pl>, A: GameAction, Pl: Player, Ro: RolloutPolicy<S, A, Pl> {
+    match rollout_policy.rollout(game, config.max_rollout_depth, rng) {
+        true => game.get_reward_for_player(player),
+        false => game.evaluate(player)
+    }
+}
+
+/// Plays out the game from the given state with the given rollout policy,
+/// and returns the rewards (or static evaluations, if the rollout was cut short) for all the given players.
+pub(crate) fn simulate_max_n<S, A, Pl, Ro>(game: &mut S, rollout_policy: &Ro, config: &SearchConfig, players: &[Pl], rng: &mut Rng) -> Vec<f32> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Ro: RolloutPolicy<S, A, Pl> {
+    match rollout_policy.rollout(game, config.max_rollout_depth, rng) {
+        true => game.get_rewards(players),
+        false => players.iter().map(|&player| game.evaluate(player)).collect()
+    }
+}
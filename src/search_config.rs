@@ -0,0 +1,93 @@
+//! Contains the SearchConfig and SearchContext structs, which hold the settings of a search,
+//! as well as the ExpansionStrategy enum and the ProgressiveWidening struct.
+
+use crate::final_selection::FinalSelection;
+
+/// Represents the settings used while running MCTS iterations.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchConfig {
+    /// The maximum number of actions to play during a rollout.
+    /// Rollouts that are cut short are scored with `GameState::evaluate` instead of the final reward.
+    /// None plays every rollout until the game is over.
+    pub max_rollout_depth: Option<usize>,
+    /// Whether to run information set search (SO-ISMCTS) for games with hidden information.
+    /// Each iteration then searches a state sampled with `GameState::determinize`,
+    /// and children are only selected when their action is legal in that state.
+    pub information_set_search: bool,
+    /// How many children are added when a leaf node is expanded.
+    pub expansion: ExpansionStrategy,
+    /// Whether to run MCTS-Solver, which proves the game-theoretic value of nodes from terminal states,
+    /// stops sampling proven nodes and never selects children proven to lose for the player to move.
+    pub solver: bool,
+    /// Whether to run score-bounded MCTS, which keeps pessimistic and optimistic bounds on the reward of each node,
+    /// derived from terminal states, never selects children that can't change the reward of their parent,
+    /// and stops sampling nodes whose bounds meet.
+    pub score_bounds: bool,
+    /// How the action to play is chosen among the children of the root node.
+    pub final_selection: FinalSelection,
+    /// The progressive widening of the actions of each node, if any, which only applies to incremental expansion.
+    /// Nodes whose children are all allowed descend into them instead of adding another one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub progressive_widening: Option<ProgressiveWidening>,
+    /// The progressive widening of the outcomes of random events, if any (double progressive widening).
+    /// Chance nodes whose children are all allowed sample among them, by probability, instead of among every outcome.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub outcome_widening: Option<ProgressiveWidening>
+}
+
+/// Represents how a leaf node is expanded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExpansionStrategy {
+    /// Adds one child for a random untried action per visit, and plays out the game from that child.
+    #[default]
+    Incremental,
+    /// Adds a child for every legal action at once, and plays out the game from the node itself.
+    /// The prior provider and the rollout policy are then called once per node, as with AlphaZero-style evaluators.
+    All
+}
+
+/// Represents progressive widening, which only lets a node have `constant * visits^exponent` children (and at least one),
+/// so that nodes with huge numbers of actions or outcomes are still searched in depth.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgressiveWidening {
+    /// The number of children allowed per visit raised to the exponent.
+    pub constant: f32,
+    /// The exponent of the visits, between 0 and 1. Lower exponents widen more slowly.
+    pub exponent: f32
+}
+
+impl ProgressiveWidening {
+    pub fn new(constant: f32, exponent: f32) -> Self {
+        assert!(constant > 0.0, "Widening constant must be positive");
+        assert!(exponent > 0.0 && exponent < 1.0, "Widening exponent must be between 0 and 1");
+        Self {
+            constant,
+            exponent
+        }
+    }
+
+    /// Returns whether a node with the given number of children and visits may add another child.
+    pub fn allows_child(&self, child_count: usize, visits: u32) -> bool {
+        child_count == 0 || (child_count as f32) < self.constant * (visits as f32).powf(self.exponent)
+    }
+}
+
+/// Bundles the policies and settings needed to run MCTS iterations, besides the tree and the game state.
+pub struct SearchContext<'a, Pl, Po, Ro, Pr> {
+    /// The tree policy used to select children.
+    pub tree_policy: &'a Po,
+    /// The rollout policy used to play out the game from newly expanded nodes.
+    pub rollout_policy: &'a Ro,
+    /// The prior provider used to set the priors of newly expanded nodes.
+    pub prior_provider: &'a Pr,
+    /// The settings of the search.
+    pub config: &'a SearchConfig,
+    /// The players whose rewards are tracked in max^n search. Empty for the default paranoid search.
+    pub players: &'a [Pl],
+    /// Whether leaf nodes can be expanded. False once the tree has reached a node limit that stops expansion,
+    /// in which case leaves are played out from directly, and leaves that already have children descend into them.
+    pub can_expand: bool
+}
@@ -0,0 +1,110 @@
+//! Contains the SearchLimit struct, which defines the budget of a search, and the tracker that enforces it.
+
+use std::time::{Duration, Instant};
+
+/// The wall-clock time aimed for between two checks of the clock when searching with a time limit.
+const CLOCK_CHECK_PERIOD: Duration = Duration::from_millis(1);
+
+/// Represents the budget of a search. The search stops as soon as any of the set limits is reached.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimit {
+    /// The maximum number of iterations to run.
+    pub iterations: Option<usize>,
+    /// The maximum wall-clock time to search for.
+    pub duration: Option<Duration>,
+    /// The maximum number of nodes in the search tree, as counted by `SearchNode::get_node_count`.
+    pub nodes: Option<u32>
+}
+
+impl SearchLimit {
+    /// Constructs a limit on the number of iterations.
+    pub fn iterations(iterations: usize) -> Self {
+        Self::default().with_iterations(iterations)
+    }
+
+    /// Constructs a limit on the wall-clock time.
+    pub fn duration(duration: Duration) -> Self {
+        Self::default().with_duration(duration)
+    }
+
+    /// Constructs a limit on the number of nodes in the search tree.
+    pub fn nodes(nodes: u32) -> Self {
+        Self::default().with_nodes(nodes)
+    }
+
+    /// Adds a limit on the number of iterations.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = Some(iterations);
+        self
+    }
+
+    /// Adds a limit on the wall-clock time.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Adds a limit on the number of nodes in the search tree.
+    pub fn with_nodes(mut self, nodes: u32) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Returns whether no limit is set, meaning a search would never stop.
+    pub fn is_unbounded(&self) -> bool {
+        self.iterations.is_none() && self.duration.is_none() && self.nodes.is_none()
+    }
+}
+
+/// Tracks the iterations and time of a running search against its limit.
+/// The clock is checked on a stride of iterations scaled from the measured iteration rate, so that it is read
+/// about once per `CLOCK_CHECK_PERIOD` whether iterations take microseconds or seconds.
+pub(crate) struct LimitTracker {
+    limit: SearchLimit,
+    start: Instant,
+    iterations: usize,
+    next_clock_check: usize
+}
+
+impl LimitTracker {
+    /// Starts tracking a search with the given limit, which must set at least one bound.
+    pub(crate) fn start(limit: SearchLimit) -> Self {
+        assert!(!limit.is_unbounded(), "Search limit must set at least one bound");
+        LimitTracker {
+            limit,
+            start: Instant::now(),
+            iterations: 0,
+            next_clock_check: 0
+        }
+    }
+
+    /// Returns whether another iteration may run, given the number of nodes in the search tree, and counts it if so.
+    pub(crate) fn next_iteration(&mut self, node_count: u32) -> bool {
+        if self.limit.iterations.is_some_and(|max_iterations| self.iterations >= max_iterations) {
+            return false;
+        }
+        if self.limit.nodes.is_some_and(|max_nodes| node_count >= max_nodes) {
+            return false;
+        }
+        if let Some(duration) = self.limit.duration {
+            if self.iterations >= self.next_clock_check {
+                let elapsed = self.start.elapsed();
+                if elapsed >= duration {
+                    return false;
+                }
+                // Aim the next check one period ahead (or at the deadline, if sooner), at most doubling the iterations so far.
+                let period = CLOCK_CHECK_PERIOD.min(duration - elapsed).as_secs_f64();
+                let iterations_per_second = self.iterations as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+                let stride = ((iterations_per_second * period) as usize).clamp(1, self.iterations.max(1));
+                self.next_clock_check = self.iterations + stride;
+            }
+        }
+        self.iterations += 1;
+        true
+    }
+
+    /// Returns the number of iterations that were allowed to run.
+    pub(crate) fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+}
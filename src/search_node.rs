@@ -1,10 +1,18 @@
 //! Contains the SearchNode struct, which represents a node in the search tree.
 
 use std::fmt;
-use crate::game::{GameAction, GameState, Player};
+use fastrand::Rng;
+use crate::game::{choose_untried_action, sample_chance_outcome, GameAction, GameState, Player};
+use crate::rollout_policy::{simulate, simulate_max_n, RolloutPolicy};
+use crate::prior_provider::PriorProvider;
+use crate::search_config::{ExpansionStrategy, ProgressiveWidening, SearchConfig, SearchContext};
+use crate::transposition_table::TranspositionTable;
+use crate::tree_format::{FormatOptions, TreeDisplay};
 use crate::tree_policy::TreePolicy;
 
 /// Represents the state of a node in the search tree.
+/// With the `serde` feature, the tree is serialized as a flat pre-order list of nodes, each with its number of children,
+/// so that neither saving nor loading it recurses however deep it is.
 pub struct SearchNode<A, Pl> where A: GameAction, Pl: Player {
     /// The action that this node represents. Only None for the root node.
     pub action: Option<A>,
@@ -17,63 +25,812 @@ pub struct SearchNode<A, Pl> where A: GameAction, Pl: Player {
     /// The number of times this node has been visited.
     pub visits: u32,
     /// The total value of this node as a result of rollouts.
-    pub total_value: f32
+    /// In max^n search, this is the total reward of the player who chose this node's action.
+    pub total_value: f32,
+    /// The total of the squared rewards behind `total_value`, from which the spread of this node's value is estimated.
+    pub squared_value: f32,
+    /// The total reward of each player as a result of rollouts, in the order of the max^n players.
+    /// Empty unless searching with max^n.
+    pub rewards: Vec<f32>,
+    /// The number of times this node's action was legal when its parent was visited.
+    /// Only counted in information set search, where it replaces the parent's visits in selection.
+    pub availability: u32,
+    /// The prior probability of this node's action, as given by the prior provider when the node was added.
+    /// For outcomes of random events, this is the probability of the outcome instead.
+    pub prior: f32,
+    /// The key of this node's game state in the transposition table, whose statistics this node then mirrors.
+    /// None if not searching with transpositions, or if the game state couldn't be added to the table.
+    pub transposition_key: Option<u64>,
+    /// The proven reward of this node for the root player, under optimal play, as found by MCTS-Solver.
+    /// A positive reward is a proven win, a negative one a proven loss and zero a proven draw. None if not proven.
+    pub proven_value: Option<f32>,
+    /// The lowest reward this node can have for the root player under optimal play, as found by score-bounded MCTS.
+    pub pessimistic_bound: f32,
+    /// The highest reward this node can have for the root player under optimal play, as found by score-bounded MCTS.
+    pub optimistic_bound: f32
+}
+
+/// The serialized form of a search node: its fields other than its children, and its number of children,
+/// which follow it in the pre-order list of nodes.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FlatSearchNode<A, Pl> {
+    action: Option<A>,
+    child_count: usize,
+    root_player: Pl,
+    state: NodeState,
+    visits: u32,
+    total_value: f32,
+    squared_value: f32,
+    rewards: Vec<f32>,
+    availability: u32,
+    prior: f32,
+    transposition_key: Option<u64>,
+    proven_value: Option<f32>,
+    #[serde(serialize_with = "serialize_bound", deserialize_with = "deserialize_pessimistic_bound")]
+    pessimistic_bound: f32,
+    #[serde(serialize_with = "serialize_bound", deserialize_with = "deserialize_optimistic_bound")]
+    optimistic_bound: f32
+}
+
+/// Serializes the tree rooted at this node as a flat pre-order list of nodes.
+#[cfg(feature = "serde")]
+impl<A, Pl> serde::Serialize for SearchNode<A, Pl> where A: GameAction + serde::Serialize, Pl: Player + serde::Serialize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+        let mut stack = vec![self];
+        serializer.collect_seq(std::iter::from_fn(|| {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter().rev());
+            Some(FlatSearchNode {
+                action: node.action,
+                child_count: node.children.len(),
+                root_player: node.root_player,
+                state: node.state,
+                visits: node.visits,
+                total_value: node.total_value,
+                squared_value: node.squared_value,
+                rewards: node.rewards.clone(),
+                availability: node.availability,
+                prior: node.prior,
+                transposition_key: node.transposition_key,
+                proven_value: node.proven_value,
+                pessimistic_bound: node.pessimistic_bound,
+                optimistic_bound: node.optimistic_bound
+            })
+        }))
+    }
+}
+
+/// Deserializes a tree from a flat pre-order list of nodes, attaching each node to its parent once all its children have been read.
+#[cfg(feature = "serde")]
+impl<'de, A, Pl> serde::Deserialize<'de> for SearchNode<A, Pl> where A: GameAction + serde::Deserialize<'de>, Pl: Player + serde::Deserialize<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+        let flat_nodes = <Vec<FlatSearchNode<A, Pl>> as serde::Deserialize>::deserialize(deserializer)?;
+        let node_count = flat_nodes.len();
+        // The nodes whose children are still being read, with the number of children they are missing.
+        let mut stack: Vec<(SearchNode<A, Pl>, usize)> = Vec::new();
+        for (index, flat_node) in flat_nodes.into_iter().enumerate() {
+            if flat_node.child_count > node_count - index - 1 {
+                return Err(serde::de::Error::custom(format!("Expected at most {} children, found {}", node_count - index - 1, flat_node.child_count)));
+            }
+            let node = SearchNode {
+                action: flat_node.action,
+                children: Vec::with_capacity(flat_node.child_count),
+                root_player: flat_node.root_player,
+                state: flat_node.state,
+                visits: flat_node.visits,
+                total_value: flat_node.total_value,
+                squared_value: flat_node.squared_value,
+                rewards: flat_node.rewards,
+                availability: flat_node.availability,
+                prior: flat_node.prior,
+                transposition_key: flat_node.transposition_key,
+                proven_value: flat_node.proven_value,
+                pessimistic_bound: flat_node.pessimistic_bound,
+                optimistic_bound: flat_node.optimistic_bound
+            };
+            stack.push((node, flat_node.child_count));
+            while stack.last().is_some_and(|(_, missing_children)| *missing_children == 0) {
+                let (node, _) = stack.pop().expect("Expected stack to be non-empty");
+                match stack.last_mut() {
+                    Some((parent, missing_children)) => {
+                        parent.children.push(node);
+                        *missing_children -= 1;
+                    },
+                    None if index + 1 == node_count => return Ok(node),
+                    None => return Err(serde::de::Error::custom(format!("Expected {} search nodes, found {}", index + 1, node_count)))
+                }
+            }
+        }
+        Err(serde::de::Error::custom("Expected more search nodes"))
+    }
+}
+
+/// Serializes a score bound, with None for an unbounded side, since formats such as JSON can't represent infinities.
+#[cfg(feature = "serde")]
+fn serialize_bound<S>(bound: &f32, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+    serde::Serialize::serialize(&Some(*bound).filter(|bound| bound.is_finite()), serializer)
+}
+
+/// Deserializes a pessimistic score bound, which is negative infinity if unbounded.
+#[cfg(feature = "serde")]
+fn deserialize_pessimistic_bound<'de, D>(deserializer: D) -> Result<f32, D::Error> where D: serde::Deserializer<'de> {
+    Ok(<Option<f32> as serde::Deserialize>::deserialize(deserializer)?.unwrap_or(f32::NEG_INFINITY))
+}
+
+/// Deserializes an optimistic score bound, which is infinity if unbounded.
+#[cfg(feature = "serde")]
+fn deserialize_optimistic_bound<'de, D>(deserializer: D) -> Result<f32, D::Error> where D: serde::Deserializer<'de> {
+    Ok(<Option<f32> as serde::Deserialize>::deserialize(deserializer)?.unwrap_or(f32::INFINITY))
 }
 
 impl<A, Pl> SearchNode<A, Pl> where A: GameAction, Pl: Player {
     /// Constructs a new search node with the given action and root_player.
     pub fn new(action: Option<A>, root_player: Pl) -> SearchNode<A, Pl> {
         SearchNode::<A, Pl> {
-            action: action,
+            action,
             children: Vec::new(),
-            root_player: root_player,
+            root_player,
             state: NodeState::ExpandableLeaf,
             visits: 0,
-            total_value: 0.0
+            total_value: 0.0,
+            squared_value: 0.0,
+            rewards: Vec::new(),
+            availability: 0,
+            prior: 1.0,
+            transposition_key: None,
+            proven_value: None,
+            pessimistic_bound: f32::NEG_INFINITY,
+            optimistic_bound: f32::INFINITY
         }
     }
 
     /// Runs a single iteration of the MCTS algorithm.
     /// Returns the reward for the player whose turn it was at the root node (initial game position).
-    pub fn run_iteration<S, Po>(&mut self, game: &mut S, tree_policy: &Po) -> f32 where S: GameState<A, Pl>, Po: TreePolicy<A, Pl> {
-        let delta = match self.state {
-            NodeState::ExpandableLeaf => {
-                let root_player = self.root_player;
-                match self.expand(game) {
-                    Some(best_child) =>  {
-                        game.apply_action(&best_child.action.expect("Expected child node to have action"));
-                        let mut available = game.get_actions();
-                        while available.len() > 0 {
-                            let action = available[fastrand::usize(0..available.len())];
-                            game.apply_action(&action);
-                            available = game.get_actions();
+    pub fn run_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, rng: &mut Rng) -> f32 where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        self.run_counted_iteration(game, context, rng).0
+    }
+
+    /// Runs a single iteration of the MCTS algorithm, and also returns the number of nodes it added.
+    /// The selected path is kept as the indices of its children, and walked again from this node to back up the reward,
+    /// so that neither step recurses and the depth of the tree is only bounded by memory.
+    pub(crate) fn run_counted_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, rng: &mut Rng) -> (f32, u32) where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let mut path = Vec::new();
+        let mut node = &mut *self;
+        let mut added_nodes = 0;
+        let delta = loop {
+            if let Some(value) = node.proven_value {
+                node.record_visit(value);
+                break value;
+            }
+            node.detect_chance(game);
+            let available_actions = node.refresh_information_set_state(game, context.config);
+            let is_root_player_turn = game.get_turn() == node.root_player;
+            let index = match node.state {
+                NodeState::ExpandableLeaf if !context.can_expand && !node.has_selectable_child(&available_actions) => {
+                    let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                    node.back_up(reward, is_root_player_turn, context.config);
+                    break reward;
+                },
+                NodeState::ExpandableLeaf if context.can_expand && context.config.expansion == ExpansionStrategy::All => {
+                    let child_count = node.children.len();
+                    match node.expand_all(game, context.prior_provider) {
+                        true => {
+                            added_nodes += (node.children.len() - child_count) as u32;
+                            if context.config.solver || context.config.score_bounds {
+                                node.prove_terminal_children(game, child_count);
+                            }
+                            let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                            node.back_up(reward, is_root_player_turn, context.config);
+                            break reward;
+                        },
+                        false => {
+                            let reward = game.get_reward_for_player(node.root_player);
+                            if context.config.solver || context.config.score_bounds {
+                                node.prove(reward);
+                            }
+                            break reward;
                         }
-                        let reward = game.get_reward_for_player(root_player);
-                        best_child.visits += 1;
-                        best_child.total_value += reward;
-                        reward
-                    },
-                    None => return game.get_reward_for_player(root_player)
+                    }
+                },
+                NodeState::ExpandableLeaf if context.can_expand && node.can_widen(context.config.progressive_widening, &available_actions) => {
+                    if let Some(actions) = &available_actions {
+                        node.count_availability(actions);
+                    }
+                    let root_player = node.root_player;
+                    let reward = match node.expand(game, context.prior_provider, rng) {
+                        Some(best_child) =>  {
+                            added_nodes += 1;
+                            best_child.availability += u32::from(available_actions.is_some());
+                            game.apply_action(&best_child.action.expect("Expected child node to have action"));
+                            if context.config.solver || context.config.score_bounds {
+                                best_child.prove_if_terminal(game);
+                            }
+                            let reward = simulate(game, context.rollout_policy, context.config, root_player, rng);
+                            best_child.visits += 1;
+                            best_child.total_value += reward;
+                            best_child.squared_value += reward.powi(2);
+                            reward
+                        },
+                        None => {
+                            let reward = game.get_reward_for_player(root_player);
+                            if context.config.solver || context.config.score_bounds {
+                                node.prove(reward);
+                            }
+                            break reward;
+                        }
+                    };
+                    node.back_up(reward, is_root_player_turn, context.config);
+                    break reward;
+                },
+                NodeState::TerminalLeaf => {
+                    let reward = game.get_reward_for_player(node.root_player);
+                    if context.config.solver || context.config.score_bounds {
+                        node.prove(reward);
+                    }
+                    node.back_up(reward, is_root_player_turn, context.config);
+                    break reward;
+                },
+                // Leaves that can't be expanded any further, or not yet under progressive widening, descend into their existing children instead.
+                NodeState::Expanded | NodeState::ExpandableLeaf => {
+                    match &available_actions {
+                        Some(actions) => node.select_compatible_child(context.tree_policy, actions, is_root_player_turn),
+                        None if context.config.solver || context.config.score_bounds => node.select_unpruned_child(context.tree_policy, context.config, is_root_player_turn),
+                        None => node.select_child(context.tree_policy, is_root_player_turn)
+                    }
+                },
+                NodeState::Chance => {
+                    let (index, is_new) = node.sample_chance_child(game, context.config.outcome_widening, rng);
+                    if is_new {
+                        let child = &mut node.children[index];
+                        game.apply_action(&child.action.expect("Expected child node to have action"));
+                        let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                        match context.can_expand {
+                            true => {
+                                child.visits += 1;
+                                child.total_value += reward;
+                                child.squared_value += reward.powi(2);
+                                added_nodes += 1;
+                            },
+                            false => {
+                                node.children.pop();
+                            }
+                        }
+                        node.back_up(reward, is_root_player_turn, context.config);
+                        break reward;
+                    }
+                    index
                 }
-            },
-            NodeState::TerminalLeaf => {
-                game.get_reward_for_player(self.root_player)
-            },
-            NodeState::Expanded => {
-                let child = tree_policy.select_child(self, game.get_turn() == self.root_player);
-                game.apply_action(&child.action.expect("Expected child node to have action"));
-                child.run_iteration(game, tree_policy)
-            }
+            };
+            game.apply_action(&node.children[index].action.expect("Expected child node to have action"));
+            path.push((index, is_root_player_turn));
+            node = &mut node.children[index];
         };
+        // Visits don't depend on each other, so they are recorded in a single walk down the path.
+        let mut node = &mut *self;
+        for &(index, _) in &path {
+            node.record_visit(delta);
+            node = &mut node.children[index];
+        }
+        // Proofs and score bounds are derived from the children, so they are updated from the bottom of the path up,
+        // walking down to each node again, until they stop changing.
+        if context.config.solver || context.config.score_bounds {
+            for depth in (0..path.len()).rev() {
+                let node = self.get_descendant_mut(path[..depth].iter().map(|&(index, _)| index));
+                if !node.update_proof_and_bounds(path[depth].1, context.config) {
+                    break;
+                }
+            }
+        }
+        (delta, added_nodes)
+    }
+
+    /// Returns the descendant of this node reached by following the given child indices.
+    fn get_descendant_mut(&mut self, path: impl Iterator<Item = usize>) -> &mut SearchNode<A, Pl> {
+        path.fold(self, |node, index| &mut node.children[index])
+    }
+
+    /// Records a visit to this node with the given reward, once the iteration has been backed up through its children.
+    /// Updates the proof and score bounds of this node first, if enabled.
+    fn back_up(&mut self, delta: f32, is_root_player_turn: bool, config: &SearchConfig) {
+        self.update_proof_and_bounds(is_root_player_turn, config);
+        self.record_visit(delta);
+    }
+
+    /// Records a visit to this node with the given reward.
+    fn record_visit(&mut self, delta: f32) {
         self.visits += 1;
         self.total_value += delta;
-        delta
+        self.squared_value += delta.powi(2);
+    }
+
+    /// Updates the proof and score bounds of this node from its children, if enabled, and returns whether either changed.
+    fn update_proof_and_bounds(&mut self, is_root_player_turn: bool, config: &SearchConfig) -> bool {
+        if self.state == NodeState::Chance {
+            return false;
+        }
+        let previous = (self.proven_value, self.pessimistic_bound, self.optimistic_bound);
+        if config.solver {
+            self.update_proof(is_root_player_turn);
+        }
+        if config.score_bounds {
+            self.update_bounds(is_root_player_turn);
+        }
+        previous != (self.proven_value, self.pessimistic_bound, self.optimistic_bound)
+    }
+
+    /// Selects a child with the tree policy, and returns its index.
+    fn select_child<Po>(&mut self, tree_policy: &Po, is_root_player_turn: bool) -> usize where Po: TreePolicy<A, Pl> {
+        let action = tree_policy.select_child(self, is_root_player_turn).action;
+        self.get_child_index(action)
+    }
+
+    /// Returns whether this node has a child that can be selected, i.e. one whose action is among the available actions
+    /// in information set search, and any child otherwise.
+    fn has_selectable_child(&self, available_actions: &Option<Vec<A>>) -> bool {
+        match available_actions {
+            Some(actions) => self.children.iter().any(|child| actions.contains(&child.action.expect("Child node without action"))),
+            None => !self.children.is_empty()
+        }
+    }
+
+    /// Returns whether a child can be added to this node under the given progressive widening, if any,
+    /// which always allows one if the node has no child that can be selected.
+    fn can_widen(&self, widening: Option<ProgressiveWidening>, available_actions: &Option<Vec<A>>) -> bool {
+        match widening {
+            Some(widening) => !self.has_selectable_child(available_actions) || widening.allows_child(self.children.len(), self.visits),
+            None => true
+        }
+    }
+
+    /// Returns the index of the child with the given action.
+    fn get_child_index(&self, action: Option<A>) -> usize {
+        self.children.iter().position(|child| child.action == action).expect("No best child found")
+    }
+
+    /// Marks this newly added node as a proven TerminalLeaf if the game is over in the given state, which it represents.
+    fn prove_if_terminal<S>(&mut self, game: &S) where S: GameState<A, Pl> {
+        if game.get_chance_outcomes().is_none() && game.get_actions().is_empty() {
+            self.state = NodeState::TerminalLeaf;
+            self.prove(game.get_reward_for_player(self.root_player));
+        }
+    }
+
+    /// Marks the children from the given index on, just added to this node in the given state, as proven TerminalLeaf nodes
+    /// if the game is over after their actions.
+    fn prove_terminal_children<S>(&mut self, game: &S, first_child: usize) where S: GameState<A, Pl> {
+        for child in &mut self.children[first_child..] {
+            let mut child_game = game.clone();
+            child_game.apply_action(&child.action.expect("Expected child node to have action"));
+            child.prove_if_terminal(&child_game);
+        }
+    }
+
+    /// Marks this node as proven to have the given exact reward.
+    fn prove(&mut self, value: f32) {
+        self.proven_value = Some(value);
+        self.pessimistic_bound = value;
+        self.optimistic_bound = value;
+    }
+
+    /// Proves the value of this node from its children, if possible.
+    /// A node is a proven win for the player to move if any child is, and is proven otherwise once all its children are.
+    fn update_proof(&mut self, is_root_player_turn: bool) {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let best_proven = self.children.iter()
+            .filter_map(|child| child.proven_value)
+            .map(|value| sign*value)
+            .reduce(f32::max);
+        let all_proven = self.state == NodeState::Expanded && self.children.iter().all(|child| child.proven_value.is_some());
+        if let Some(best_proven) = best_proven {
+            if best_proven > 0.0 || all_proven {
+                self.proven_value = Some(sign*best_proven);
+            }
+        }
+    }
+
+    /// Updates the score bounds of this node from those of its children.
+    /// The player to move guarantees the best pessimistic bound of any child,
+    /// and can't do better than the best optimistic bound once every child has been added. The opponent does the reverse.
+    /// Bounds only ever tighten, so that they survive the pruning of the children they were derived from.
+    /// The node is proven once its bounds meet.
+    fn update_bounds(&mut self, is_root_player_turn: bool) {
+        if self.children.is_empty() {
+            return;
+        }
+        let is_fully_expanded = self.state == NodeState::Expanded;
+        let (pessimistic_bounds, optimistic_bounds) = (self.children.iter().map(|child| child.pessimistic_bound), self.children.iter().map(|child| child.optimistic_bound));
+        match is_root_player_turn {
+            true => {
+                self.pessimistic_bound = self.pessimistic_bound.max(pessimistic_bounds.fold(f32::NEG_INFINITY, f32::max));
+                if is_fully_expanded {
+                    self.optimistic_bound = self.optimistic_bound.min(optimistic_bounds.fold(f32::NEG_INFINITY, f32::max));
+                }
+            },
+            false => {
+                if is_fully_expanded {
+                    self.pessimistic_bound = self.pessimistic_bound.max(pessimistic_bounds.fold(f32::INFINITY, f32::min));
+                }
+                self.optimistic_bound = self.optimistic_bound.min(optimistic_bounds.fold(f32::INFINITY, f32::min));
+            }
+        }
+        if self.pessimistic_bound >= self.optimistic_bound {
+            self.prove(self.pessimistic_bound);
+        }
+    }
+
+    /// Selects a child with the tree policy and returns its index, ignoring the children that can't help the player to move:
+    /// those proven to lose with MCTS-Solver, and those whose bounds can't improve on this node's with score-bounded MCTS.
+    fn select_unpruned_child<Po>(&mut self, tree_policy: &Po, config: &SearchConfig, is_root_player_turn: bool) -> usize where Po: TreePolicy<A, Pl> {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let (pessimistic_bound, optimistic_bound) = (self.pessimistic_bound, self.optimistic_bound);
+        let is_pruned = |child: &SearchNode<A, Pl>| {
+            let is_lost = config.solver && child.proven_value.is_some_and(|value| sign*value < 0.0);
+            let is_bounded = config.score_bounds && match is_root_player_turn {
+                true => child.optimistic_bound <= pessimistic_bound,
+                false => child.pessimistic_bound >= optimistic_bound
+            };
+            is_lost || is_bounded
+        };
+        if !self.children.iter().any(is_pruned) || self.children.iter().all(is_pruned) {
+            return self.select_child(tree_policy, is_root_player_turn);
+        }
+        let (pruned, unpruned): (Vec<_>, Vec<_>) = std::mem::take(&mut self.children).into_iter().partition(is_pruned);
+        self.children = unpruned;
+        let action = tree_policy.select_child(self, is_root_player_turn).action;
+        self.children.extend(pruned);
+        self.get_child_index(action)
+    }
+
+    /// Runs a single iteration of max^n search, where every player maximizes their own reward.
+    /// Returns the rewards for the given players, which are also added to `rewards`.
+    /// The total value of each child is the reward of the player who chose it, so the tree policy always maximizes.
+    pub fn run_max_n_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, rng: &mut Rng) -> Vec<f32> where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        self.run_counted_max_n_iteration(game, context, rng).0
+    }
+
+    /// Runs a single iteration of max^n search, and also returns the number of nodes it added.
+    pub(crate) fn run_counted_max_n_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, rng: &mut Rng) -> (Vec<f32>, u32) where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let mut path = Vec::new();
+        let mut node = &mut *self;
+        let mut added_nodes = 0;
+        let rewards = loop {
+            node.detect_chance(game);
+            let available_actions = node.refresh_information_set_state(game, context.config);
+            let (index, mover) = match node.state {
+                NodeState::ExpandableLeaf if !context.can_expand && !node.has_selectable_child(&available_actions) => {
+                    let rewards = simulate_max_n(game, context.rollout_policy, context.config, context.players, rng);
+                    node.visits += 1;
+                    node.add_rewards(&rewards);
+                    break rewards;
+                },
+                NodeState::ExpandableLeaf if context.can_expand && context.config.expansion == ExpansionStrategy::All => {
+                    let child_count = node.children.len();
+                    match node.expand_all(game, context.prior_provider) {
+                        true => {
+                            added_nodes += (node.children.len() - child_count) as u32;
+                            let rewards = simulate_max_n(game, context.rollout_policy, context.config, context.players, rng);
+                            node.visits += 1;
+                            node.add_rewards(&rewards);
+                            break rewards;
+                        },
+                        false => {
+                            let rewards = game.get_rewards(context.players);
+                            node.visits += 1;
+                            node.add_rewards(&rewards);
+                            break rewards;
+                        }
+                    }
+                },
+                NodeState::ExpandableLeaf if context.can_expand && node.can_widen(context.config.progressive_widening, &available_actions) => {
+                    if let Some(actions) = &available_actions {
+                        node.count_availability(actions);
+                    }
+                    let mover = get_player_index(context.players, game.get_turn());
+                    let rewards = match node.expand(game, context.prior_provider, rng) {
+                        Some(best_child) => {
+                            added_nodes += 1;
+                            best_child.availability += u32::from(available_actions.is_some());
+                            game.apply_action(&best_child.action.expect("Expected child node to have action"));
+                            let rewards = simulate_max_n(game, context.rollout_policy, context.config, context.players, rng);
+                            best_child.visits += 1;
+                            best_child.total_value += rewards[mover];
+                            best_child.squared_value += rewards[mover].powi(2);
+                            best_child.add_rewards(&rewards);
+                            rewards
+                        },
+                        None => {
+                            let rewards = game.get_rewards(context.players);
+                            node.visits += 1;
+                            node.add_rewards(&rewards);
+                            break rewards;
+                        }
+                    };
+                    node.visits += 1;
+                    node.add_rewards(&rewards);
+                    break rewards;
+                },
+                NodeState::TerminalLeaf => {
+                    let rewards = game.get_rewards(context.players);
+                    node.visits += 1;
+                    node.add_rewards(&rewards);
+                    break rewards;
+                },
+                NodeState::Expanded | NodeState::ExpandableLeaf => {
+                    let mover = get_player_index(context.players, game.get_turn());
+                    let index = match &available_actions {
+                        Some(actions) => node.select_compatible_child(context.tree_policy, actions, true),
+                        None => node.select_child(context.tree_policy, true)
+                    };
+                    (index, mover)
+                },
+                NodeState::Chance => {
+                    let mover = get_player_index(context.players, game.get_turn());
+                    let (index, is_new) = node.sample_chance_child(game, context.config.outcome_widening, rng);
+                    if is_new {
+                        let child = &mut node.children[index];
+                        game.apply_action(&child.action.expect("Expected child node to have action"));
+                        let rewards = simulate_max_n(game, context.rollout_policy, context.config, context.players, rng);
+                        match context.can_expand {
+                            true => {
+                                child.visits += 1;
+                                child.total_value += rewards[mover];
+                                child.squared_value += rewards[mover].powi(2);
+                                child.add_rewards(&rewards);
+                                added_nodes += 1;
+                            },
+                            false => {
+                                node.children.pop();
+                            }
+                        }
+                        node.visits += 1;
+                        node.add_rewards(&rewards);
+                        break rewards;
+                    }
+                    (index, mover)
+                }
+            };
+            game.apply_action(&node.children[index].action.expect("Expected child node to have action"));
+            path.push((index, mover));
+            node = &mut node.children[index];
+        };
+        let mut node = &mut *self;
+        for &(index, mover) in &path {
+            node.visits += 1;
+            node.add_rewards(&rewards);
+            node = &mut node.children[index];
+            node.total_value += rewards[mover];
+            node.squared_value += rewards[mover].powi(2);
+        }
+        (rewards, added_nodes)
+    }
+
+    /// Runs a single iteration of the MCTS algorithm, sharing statistics between nodes that reach the same game state.
+    /// Tracked nodes mirror the statistics of their game state in the given table, which are backed up UCT3-style:
+    /// the value of a game state is the visit-weighted mean value of its children, through whichever path they were visited.
+    /// Returns the reward for the player whose turn it was at the root node (initial game position).
+    pub fn run_transposition_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, table: &mut TranspositionTable<S>, rng: &mut Rng) -> f32 where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        self.run_counted_transposition_iteration(game, context, table, rng).0
+    }
+
+    /// Runs a single iteration of the MCTS algorithm with transpositions, and also returns the number of nodes it added.
+    pub(crate) fn run_counted_transposition_iteration<S, Po, Ro, Pr>(&mut self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, table: &mut TranspositionTable<S>, rng: &mut Rng) -> (f32, u32) where S: GameState<A, Pl>, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let mut path = Vec::new();
+        let mut node = &mut *self;
+        let mut added_nodes = 0;
+        let delta = loop {
+            node.detect_chance(game);
+            if node.transposition_key.is_none() {
+                node.transposition_key = table.find_or_insert(game);
+            }
+            let index = match node.state {
+                NodeState::ExpandableLeaf if !context.can_expand && node.children.is_empty() => {
+                    let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                    node.back_up_transposition(reward, table);
+                    break reward;
+                },
+                NodeState::ExpandableLeaf if context.can_expand && context.config.expansion == ExpansionStrategy::All => {
+                    let child_count = node.children.len();
+                    match node.expand_all(game, context.prior_provider) {
+                        true => {
+                            added_nodes += (node.children.len() - child_count) as u32;
+                            node.refresh_transposed_children(table);
+                            let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                            node.back_up_transposition(reward, table);
+                            break reward;
+                        },
+                        false => break game.get_reward_for_player(node.root_player)
+                    }
+                },
+                NodeState::ExpandableLeaf if context.can_expand && node.can_widen(context.config.progressive_widening, &None) => {
+                    let root_player = node.root_player;
+                    let reward = match node.expand(game, context.prior_provider, rng) {
+                        Some(best_child) => {
+                            added_nodes += 1;
+                            game.apply_action(&best_child.action.expect("Expected child node to have action"));
+                            best_child.transposition_key = table.find_or_insert(game);
+                            let reward = simulate(game, context.rollout_policy, context.config, root_player, rng);
+                            best_child.back_up_transposition(reward, table);
+                            reward
+                        },
+                        None => break game.get_reward_for_player(root_player)
+                    };
+                    node.back_up_transposition(reward, table);
+                    break reward;
+                },
+                NodeState::TerminalLeaf => {
+                    let reward = game.get_reward_for_player(node.root_player);
+                    node.back_up_transposition(reward, table);
+                    break reward;
+                },
+                NodeState::Expanded | NodeState::ExpandableLeaf => {
+                    node.refresh_transposed_children(table);
+                    let is_root_player_turn = game.get_turn() == node.root_player;
+                    node.select_child(context.tree_policy, is_root_player_turn)
+                },
+                NodeState::Chance => {
+                    node.refresh_transposed_children(table);
+                    let (index, is_new) = node.sample_chance_child(game, context.config.outcome_widening, rng);
+                    if is_new {
+                        let child = &mut node.children[index];
+                        game.apply_action(&child.action.expect("Expected child node to have action"));
+                        let reward = match context.can_expand {
+                            true => {
+                                child.transposition_key = table.find_or_insert(game);
+                                let reward = simulate(game, context.rollout_policy, context.config, child.root_player, rng);
+                                child.back_up_transposition(reward, table);
+                                added_nodes += 1;
+                                reward
+                            },
+                            false => {
+                                let reward = simulate(game, context.rollout_policy, context.config, child.root_player, rng);
+                                node.children.pop();
+                                reward
+                            }
+                        };
+                        node.back_up_transposition(reward, table);
+                        break reward;
+                    }
+                    index
+                }
+            };
+            game.apply_action(&node.children[index].action.expect("Expected child node to have action"));
+            path.push(index);
+            node = &mut node.children[index];
+        };
+        // The value of a tracked node is derived from its children, so the path is backed up from the bottom up,
+        // walking down to each node again.
+        for depth in (0..path.len()).rev() {
+            self.get_descendant_mut(path[..depth].iter().copied()).back_up_transposition(delta, table);
+        }
+        (delta, added_nodes)
+    }
+
+    /// Copies the statistics of each tracked child's game state from the transposition table,
+    /// as they may have changed through other paths since this node was last visited.
+    fn refresh_transposed_children<S>(&mut self, table: &TranspositionTable<S>) where S: Clone {
+        for child in self.children.iter_mut() {
+            if let Some((visits, value)) = child.transposition_key.and_then(|key| table.get(key)) {
+                child.squared_value = child.get_mean_square() * visits as f32;
+                child.visits = visits;
+                child.total_value = value * visits as f32;
+            }
+        }
+    }
+
+    /// Records a visit to this node with the given reward.
+    /// If this node is tracked, the visit is recorded for its game state in the transposition table instead,
+    /// whose value becomes the mean value of the visited children (or the mean reward, if there are none).
+    fn back_up_transposition<S>(&mut self, reward: f32, table: &mut TranspositionTable<S>) where S: Clone {
+        let (key, visits, value) = match self.transposition_key.and_then(|key| table.get(key).map(|(visits, value)| (key, visits, value))) {
+            Some(entry) => entry,
+            None => {
+                self.visits += 1;
+                self.total_value += reward;
+                self.squared_value += reward.powi(2);
+                return;
+            }
+        };
+        let visits = visits + 1;
+        let child_visits: u32 = self.children.iter().map(|child| child.visits).sum();
+        let value = match child_visits {
+            0 => value + (reward - value) / visits as f32,
+            _ => self.children.iter().map(|child| child.total_value).sum::<f32>() / child_visits as f32
+        };
+        table.set(key, visits, value);
+        // The table only keeps mean values, so the squared rewards seen through this node are scaled to its shared visits.
+        let mean_square = (self.squared_value + reward.powi(2)) / (self.visits + 1) as f32;
+        self.visits = visits;
+        self.total_value = value * visits as f32;
+        self.squared_value = mean_square * visits as f32;
+    }
+
+    /// Returns the mean squared reward of this node, or zero if it hasn't been visited.
+    fn get_mean_square(&self) -> f32 {
+        match self.visits {
+            0 => 0.0,
+            visits => self.squared_value / visits as f32
+        }
+    }
+
+    /// Marks this node as a Chance node if it hasn't been visited yet and the next transition is a random event.
+    fn detect_chance<S>(&mut self, game: &S) where S: GameState<A, Pl> {
+        if self.state == NodeState::ExpandableLeaf && self.children.is_empty() && game.get_chance_outcomes().is_some() {
+            self.state = NodeState::Chance;
+        }
+    }
+
+    /// In information set search, recomputes the state of this node for the current determinization,
+    /// in which a different set of actions may be legal.
+    /// Returns the legal actions, or None if not running information set search.
+    fn refresh_information_set_state<S>(&mut self, game: &S, config: &SearchConfig) -> Option<Vec<A>> where S: GameState<A, Pl> {
+        if !config.information_set_search || self.state == NodeState::Chance {
+            return None;
+        }
+        let actions = game.get_actions();
+        self.state = if actions.is_empty() {
+            NodeState::TerminalLeaf
+        } else if actions.iter().any(|action| !self.children.iter().any(|child| child.action == Some(*action))) {
+            NodeState::ExpandableLeaf
+        } else {
+            NodeState::Expanded
+        };
+        Some(actions)
+    }
+
+    /// Selects a child among those whose action is legal in the current determinization, counting their availability.
+    /// Returns the index of the selected child.
+    fn select_compatible_child<Po>(&mut self, tree_policy: &Po, available_actions: &[A], is_root_player_turn: bool) -> usize where Po: TreePolicy<A, Pl> {
+        self.count_availability(available_actions);
+        let action = tree_policy.select_available_child(self, available_actions, is_root_player_turn).action;
+        self.get_child_index(action)
+    }
+
+    /// Counts a visit in which each child whose action is among the available actions could have been selected.
+    fn count_availability(&mut self, available_actions: &[A]) {
+        for child in self.children.iter_mut() {
+            if available_actions.contains(&child.action.expect("Child node without action")) {
+                child.availability += 1;
+            }
+        }
+    }
+
+    /// Samples an outcome of the random event at this Chance node by probability.
+    /// Returns the index of the child node of that outcome, which is added if needed, and whether it was just added.
+    /// If the given progressive widening doesn't allow another child, the outcome is sampled among the existing children.
+    fn sample_chance_child<S>(&mut self, game: &S, widening: Option<ProgressiveWidening>, rng: &mut Rng) -> (usize, bool) where S: GameState<A, Pl> {
+        if widening.is_some_and(|widening| !widening.allows_child(self.children.len(), self.visits)) {
+            let outcomes: Vec<(usize, f32)> = self.children.iter().map(|child| child.prior).enumerate().collect();
+            return (sample_chance_outcome(&outcomes, rng).0, false);
+        }
+        let outcomes = game.get_chance_outcomes().expect("Expected chance node to have outcomes");
+        let (outcome, probability) = sample_chance_outcome(&outcomes, rng);
+        match self.children.iter().position(|child| child.action == Some(outcome)) {
+            Some(index) => (index, false),
+            None => {
+                let mut child = SearchNode::new(Some(outcome), self.root_player);
+                child.prior = probability;
+                self.children.push(child);
+                (self.children.len() - 1, true)
+            }
+        }
+    }
+
+    /// Adds the given per-player rewards to `rewards`.
+    fn add_rewards(&mut self, rewards: &[f32]) {
+        if self.rewards.len() < rewards.len() {
+            self.rewards.resize(rewards.len(), 0.0);
+        }
+        for (total, reward) in self.rewards.iter_mut().zip(rewards) {
+            *total += reward;
+        }
     }
 
     /// Adds a child node to this leaf node if it is expandable, using a random legal action.
     /// If it is not, marks this node as a TerminalLeaf.
     /// If there is only one allowed action, this node is marked as a TerminalLeaf after expansion.
-    pub fn expand<S>(&mut self, game: &S) -> Option<&mut SearchNode<A, Pl>> where S: GameState<A, Pl> {
+    /// The child's prior is taken from the priors the prior provider gives for all legal actions.
+    pub fn expand<S, Pr>(&mut self, game: &S, prior_provider: &Pr, rng: &mut Rng) -> Option<&mut SearchNode<A, Pl>> where S: GameState<A, Pl>, Pr: PriorProvider<S, A, Pl> {
         let allowed_actions = game.get_actions();
         if allowed_actions.is_empty() {
             self.state = NodeState::TerminalLeaf;
@@ -84,74 +841,153 @@ impl<A, Pl> SearchNode<A, Pl> where A: GameAction, Pl: Player {
             child_actions.push(child.action.expect("Child node without action"));
         }
         let mut candidate_actions = Vec::new();
-        for action in allowed_actions {
-            if !child_actions.contains(&action) {
-                candidate_actions.push(action);
+        for action in &allowed_actions {
+            if !child_actions.contains(action) {
+                candidate_actions.push(*action);
             }
         }
-        assert!(candidate_actions.len() > 0, "Expected at least one candidate action");
-        if candidate_actions.len() == 1 {
-            self.children.push(SearchNode::new(Some(candidate_actions[0]), self.root_player));
-            self.state = NodeState::Expanded;
+        assert!(!candidate_actions.is_empty(), "Expected at least one candidate action");
+        let action = match candidate_actions.len() {
+            1 => {
+                self.state = NodeState::Expanded;
+                candidate_actions[0]
+            },
+            _ => choose_untried_action(game, &candidate_actions, |action| !child_actions.contains(action), rng)
+        };
+        self.children.push(SearchNode::new(Some(action), self.root_player));
+        let priors = prior_provider.get_priors(game, &allowed_actions);
+        let child = self.children.last_mut().expect("Expected node to have children");
+        let index = allowed_actions.iter().position(|action| child.action == Some(*action)).expect("Expected child action to be legal");
+        child.prior = priors[index];
+        Some(child)
+    }
+
+    /// Adds a child node for every legal action that doesn't have one yet, and marks this node as Expanded.
+    /// If there are no legal actions, marks this node as a TerminalLeaf and returns false.
+    /// The prior provider is called once, for all legal actions.
+    pub fn expand_all<S, Pr>(&mut self, game: &S, prior_provider: &Pr) -> bool where S: GameState<A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let allowed_actions = game.get_actions();
+        if allowed_actions.is_empty() {
+            self.state = NodeState::TerminalLeaf;
+            return false;
         }
-        else {
-            let rand_action = candidate_actions[fastrand::usize(0..candidate_actions.len())];
-            let node = SearchNode::new(Some(rand_action), self.root_player);
-            self.children.push(node);
-            // self.children.push(SearchNode::new(Some(*candidate_actions.choose(&mut rand::thread_rng()).expect("Expected candidate actions to be non-empty"))));
+        let priors = prior_provider.get_priors(game, &allowed_actions);
+        for (&action, &prior) in allowed_actions.iter().zip(&priors) {
+            if self.children.iter().any(|child| child.action == Some(action)) {
+                continue;
+            }
+            let mut child = SearchNode::new(Some(action), self.root_player);
+            child.prior = prior;
+            self.children.push(child);
         }
-        self.children.last_mut()
+        self.state = NodeState::Expanded;
+        true
     }
 
+    /// Merges the statistics of another search tree, rooted at the same game state, into this node.
+    /// Children that represent the same action are merged in turn, the others are adopted as-is.
+    pub fn merge<S>(&mut self, other: SearchNode<A, Pl>, game: &S) where S: GameState<A, Pl> {
+        let mut stack = vec![(self, other, game.clone())];
+        while let Some((node, mut other, game)) = stack.pop() {
+            node.visits += other.visits;
+            node.total_value += other.total_value;
+            node.squared_value += other.squared_value;
+            node.availability += other.availability;
+            node.add_rewards(&other.rewards);
+            node.proven_value = node.proven_value.or(other.proven_value);
+            node.pessimistic_bound = node.pessimistic_bound.max(other.pessimistic_bound);
+            node.optimistic_bound = node.optimistic_bound.min(other.optimistic_bound);
+            if matches!(other.state, NodeState::TerminalLeaf | NodeState::Chance) {
+                node.state = other.state;
+            }
+            let mut matching_children: Vec<Option<SearchNode<A, Pl>>> = node.children.iter().map(|_| None).collect();
+            for other_child in std::mem::take(&mut other.children) {
+                match node.children.iter().position(|child| child.action == other_child.action) {
+                    Some(index) => matching_children[index] = Some(other_child),
+                    None => node.children.push(other_child)
+                }
+            }
+            if !matches!(node.state, NodeState::TerminalLeaf | NodeState::Chance) && !node.children.is_empty() {
+                node.state = match node.children.len() == game.get_actions().len() {
+                    true => NodeState::Expanded,
+                    false => NodeState::ExpandableLeaf
+                };
+            }
+            for (child, other_child) in node.children.iter_mut().zip(matching_children) {
+                if let Some(other_child) = other_child {
+                    let mut child_game = game.clone();
+                    child_game.apply_action(&child.action.expect("Child node without action"));
+                    stack.push((child, other_child, child_game));
+                }
+            }
+        }
+    }
+
+    /// Returns the number of nodes in the subtree of this node, including itself.
     pub fn get_node_count(&self) -> u32 {
-        let mut count: u32 = 1;
-        for child in &self.children {
-            count += child.get_node_count();
+        let mut count: u32 = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(&node.children);
         }
         count
     }
-}
 
-impl<A, Pl> fmt::Display for SearchNode<A, Pl> where A: GameAction, Pl: Player {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fn fmt_subtree<A: GameAction, Pl: Player>(f: &mut fmt::Formatter, node: &SearchNode<A, Pl>, indent_level :i32) -> fmt::Result {
-            for _ in 0..indent_level {
-                f.write_str("|    ")?;
+    /// Removes the subtrees of the nodes below this one with at most the given number of visits,
+    /// and marks the Expanded nodes that lost children as expandable again. Returns the number of removed nodes.
+    pub(crate) fn prune(&mut self, max_pruned_visits: u32) -> u32 {
+        let mut removed_nodes = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            let child_count = node.children.len();
+            node.children.retain(|child| match child.visits > max_pruned_visits {
+                true => true,
+                false => {
+                    removed_nodes += child.get_node_count();
+                    false
+                }
+            });
+            if node.children.len() < child_count && node.state == NodeState::Expanded {
+                node.state = NodeState::ExpandableLeaf;
             }
-            match node.action {
-                Some(a) => {
+            stack.extend(node.children.iter_mut());
+        }
+        removed_nodes
+    }
+}
 
-                    let state = match node.state {
-                        NodeState::ExpandableLeaf => "EXPANDABLE_LEAF",
-                        NodeState::TerminalLeaf => "TERMINAL_LEAF",
-                        NodeState::Expanded => "EXPANDED"
-                    };
-                    writeln!(f, "[{}] {:?} total={} visits={}", state, a, node.total_value, node.visits)?;
-                    format!("{}", a).split("\n").for_each(|line| {
-                        for _ in 0..(indent_level + 1) {
-                            f.write_str("|    ").unwrap();
-                        }
-                        writeln!(f, "{}", line).unwrap();
-                    });
-                },
-                None => writeln!(f, "[ROOT] total={} visits={}", node.total_value, node.visits)?
-            }
-            for child in &node.children {
-                fmt_subtree(f, child, indent_level + 1)?;
-            }
-            write!(f, "")
+/// Drops the subtree of a node one node at a time, as the default recursive drop would overflow the stack on deep trees.
+impl<A, Pl> Drop for SearchNode<A, Pl> where A: GameAction, Pl: Player {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
         }
-        fmt_subtree(f, self, 0)
+    }
+}
+
+/// Returns the index of the given player among the max^n players.
+fn get_player_index<Pl: Player>(players: &[Pl], player: Pl) -> usize {
+    players.iter().position(|&p| p == player).expect("Expected player to be one of the max^n players")
+}
+
+impl<A, Pl> fmt::Display for SearchNode<A, Pl> where A: GameAction, Pl: Player {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", TreeDisplay::new(self, FormatOptions::default()))
     }
 }
 
 /// Represents the state of a node in the search tree.
 #[derive(Copy,Clone,Debug,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeState {
     /// No children, but might have available actions
     ExpandableLeaf,
     /// No children, no available actions
     TerminalLeaf,
     /// Has children (already expanded)
-    Expanded
+    Expanded,
+    /// The next transition is a random event, whose sampled outcomes are the children
+    Chance
 }
@@ -0,0 +1,83 @@
+//! Contains the SearchReport struct, which summarizes the result of a search.
+
+use std::cmp::Reverse;
+use crate::game::{GameAction, Player};
+use crate::search_node::SearchNode;
+
+/// The number of standard errors on each side of the mean value in a confidence interval, for 95% confidence.
+const CONFIDENCE_Z: f32 = 1.96;
+
+/// Represents a summary of a search, as returned by `SearchTree::get_report`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchReport<A> where A: GameAction {
+    /// The number of times the root node has been visited.
+    pub visits: u32,
+    /// The statistics of each action at the root, from most to least visited.
+    pub actions: Vec<ActionReport<A>>,
+    /// The principal variation: the actions along the most visited path from the root.
+    pub principal_variation: Vec<A>
+}
+
+/// Represents the statistics of one action at the root of a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionReport<A> where A: GameAction {
+    /// The action.
+    pub action: A,
+    /// The number of times the action has been visited.
+    pub visits: u32,
+    /// The share of the root's visits that went to this action, between 0 and 1.
+    pub visit_share: f32,
+    /// The mean value of the action for the player to move at the root. Zero if the action hasn't been visited.
+    pub mean_value: f32,
+    /// The bounds of a 95% confidence interval on the mean value, from the sample standard deviation of the action's rewards.
+    /// Unbounded if the action has been visited less than twice.
+    pub confidence_interval: (f32, f32)
+}
+
+impl<A> SearchReport<A> where A: GameAction {
+    /// Builds the report of a search from its root node, with a principal variation of at most the given depth.
+    /// The value of the root node is multiplied by the given sign to get the value for the player to move.
+    pub(crate) fn new<Pl>(root: &SearchNode<A, Pl>, sign: f32, depth: usize) -> Self where Pl: Player {
+        let child_visits: u32 = root.children.iter().map(|child| child.visits).sum();
+        let mut actions: Vec<ActionReport<A>> = root.children.iter().map(|child| {
+            let mean_value = match child.visits {
+                0 => 0.0,
+                visits => sign*child.total_value / visits as f32
+            };
+            let margin = match child.visits {
+                0 | 1 => f32::INFINITY,
+                visits => {
+                    let variance = (child.squared_value - child.total_value.powi(2) / visits as f32) / (visits - 1) as f32;
+                    CONFIDENCE_Z * (variance.max(0.0) / visits as f32).sqrt()
+                }
+            };
+            ActionReport {
+                action: child.action.expect("Expected child node to have action"),
+                visits: child.visits,
+                visit_share: match child_visits {
+                    0 => 0.0,
+                    _ => child.visits as f32 / child_visits as f32
+                },
+                mean_value,
+                confidence_interval: (mean_value - margin, mean_value + margin)
+            }
+        }).collect();
+        actions.sort_by_key(|action| Reverse(action.visits));
+        let mut principal_variation = Vec::new();
+        let mut node = root;
+        while principal_variation.len() < depth {
+            match node.children.iter().filter(|child| child.visits > 0).max_by_key(|child| child.visits) {
+                Some(child) => {
+                    principal_variation.push(child.action.expect("Expected child node to have action"));
+                    node = child;
+                },
+                None => break
+            }
+        }
+        SearchReport {
+            visits: root.visits,
+            actions,
+            principal_variation
+        }
+    }
+}
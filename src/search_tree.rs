@@ -2,52 +2,599 @@
 
 use std::fmt;
 use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
+use fastrand::Rng;
+
+use crate::final_selection::FinalSelection;
 use crate::game::{GameAction, GameState, Player};
+use crate::node_limit::{NodeLimit, NodeLimitStats, NodeLimitStrategy};
+use crate::prior_provider::{PriorProvider, UniformPriorProvider};
+use crate::rollout_policy::{RolloutPolicy, UniformRolloutPolicy};
+use crate::search_config::{ExpansionStrategy, ProgressiveWidening, SearchConfig, SearchContext};
+use crate::search_limit::{LimitTracker, SearchLimit};
 use crate::search_node::*;
-use crate::tree_policy::TreePolicy;
+use crate::search_report::SearchReport;
+use crate::shared_search_node::SharedSearchNode;
+use crate::transposition_table::TranspositionTable;
+use crate::tree_export::{to_dot, to_json, ExportOptions};
+use crate::tree_format::{FormatOptions, TreeDisplay};
+use crate::tree_policy::{SharedTreePolicy, TreePolicy};
 
 /// Represents a MCTS search tree.
-pub struct SearchTree<S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>> {
+pub struct SearchTree<S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl> = UniformRolloutPolicy, Pr: PriorProvider<S, A, Pl> = UniformPriorProvider> {
     /// The root node of the search tree.
     root: SearchNode<A, Pl>,
     /// The initial game state.
     root_game_state: S,
     /// The tree policy to use.
     policy: Po,
+    /// The rollout policy to use.
+    rollout_policy: Ro,
+    /// The prior provider to use.
+    prior_provider: Pr,
+    /// The settings used while running iterations.
+    config: SearchConfig,
+    /// The random number generator used for expansion, rollouts and any other random choice.
+    rng: Rng,
+    /// The players whose rewards are tracked in max^n search. Empty for the default paranoid search.
+    players: Vec<Pl>,
+    /// The table of statistics shared by nodes that reach the same game state. None unless searching with transpositions.
+    transpositions: Option<TranspositionTable<S>>,
+    /// The number of nodes in the search tree.
+    node_count: u32,
+    /// The ceiling on the number of nodes in the search tree, if any.
+    node_limit: Option<NodeLimit>,
+    /// The statistics of the node limit.
+    node_limit_stats: NodeLimitStats
 }
 
 impl<S, A, Pl, Po> SearchTree<S, A, Pl, Po> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl> {
+    /// Constructs a new search tree from the given game state, using uniformly random rollouts and uniform priors.
     pub fn new(game: S, tree_policy: Po) -> SearchTree<S, A, Pl, Po> {
         SearchTree {
-            root: SearchNode {
-                action: None,
-                children: Vec::new(),
-                root_player: game.get_turn(),
-                state: NodeState::ExpandableLeaf,
-                visits: 0,
-                total_value: 0.0
-            },
+            root: SearchNode::new(None, game.get_turn()),
             root_game_state: game,
-            policy: tree_policy
+            policy: tree_policy,
+            rollout_policy: UniformRolloutPolicy,
+            prior_provider: UniformPriorProvider,
+            config: SearchConfig::default(),
+            rng: Rng::new(),
+            players: Vec::new(),
+            transpositions: None,
+            node_count: 1,
+            node_limit: None,
+            node_limit_stats: NodeLimitStats::default()
+        }
+    }
+}
+
+impl<S, A, Pl, Po, Ro, Pr> SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+    /// Replaces the rollout policy used to play out the game from newly expanded nodes.
+    pub fn with_rollout_policy<Ro2>(self, rollout_policy: Ro2) -> SearchTree<S, A, Pl, Po, Ro2, Pr> where Ro2: RolloutPolicy<S, A, Pl> {
+        SearchTree {
+            root: self.root,
+            root_game_state: self.root_game_state,
+            policy: self.policy,
+            rollout_policy,
+            prior_provider: self.prior_provider,
+            config: self.config,
+            rng: self.rng,
+            players: self.players,
+            transpositions: self.transpositions,
+            node_count: self.node_count,
+            node_limit: self.node_limit,
+            node_limit_stats: self.node_limit_stats
         }
     }
 
+    /// Replaces the prior provider used to set the priors of newly expanded nodes, as used by `PuctPolicy`.
+    pub fn with_prior_provider<Pr2>(self, prior_provider: Pr2) -> SearchTree<S, A, Pl, Po, Ro, Pr2> where Pr2: PriorProvider<S, A, Pl> {
+        SearchTree {
+            root: self.root,
+            root_game_state: self.root_game_state,
+            policy: self.policy,
+            rollout_policy: self.rollout_policy,
+            prior_provider,
+            config: self.config,
+            rng: self.rng,
+            players: self.players,
+            transpositions: self.transpositions,
+            node_count: self.node_count,
+            node_limit: self.node_limit,
+            node_limit_stats: self.node_limit_stats
+        }
+    }
+
+    /// Seeds the random number generator, so that searches with identical seeds produce identical trees.
+    /// Searches run with `run_tree_parallel` remain nondeterministic, as threads interleave freely.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::with_seed(seed);
+        self
+    }
+
+    /// Replaces the settings used while running iterations.
+    pub fn with_config(mut self, config: SearchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Limits rollouts to the given number of actions, after which the game is scored with `GameState::evaluate`.
+    pub fn with_max_rollout_depth(mut self, max_rollout_depth: usize) -> Self {
+        self.config.max_rollout_depth = Some(max_rollout_depth);
+        self
+    }
+
+    /// Switches to max^n search, where each of the given players maximizes their own reward,
+    /// instead of the default paranoid search, where all other players minimize the root player's reward.
+    /// Rewards are obtained from `GameState::get_rewards`.
+    pub fn with_max_n(mut self, players: Vec<Pl>) -> Self {
+        assert!(players.contains(&self.root.root_player), "Expected max^n players to include the root player");
+        assert_eq!(self.root.visits, 0, "Max^n search must be enabled before searching");
+        self.players = players;
+        self
+    }
+
+    /// Switches to information set search (SO-ISMCTS) for games with hidden information.
+    /// Each iteration searches a state sampled with `GameState::determinize` from the information set of the player to move
+    /// at the root, so that the search doesn't exploit information that player can't observe.
+    pub fn with_information_set_search(mut self) -> Self {
+        self.config.information_set_search = true;
+        self
+    }
+
+    /// Sets how leaf nodes are expanded. Defaults to `ExpansionStrategy::Incremental`.
+    pub fn with_expansion_strategy(mut self, expansion: ExpansionStrategy) -> Self {
+        self.config.expansion = expansion;
+        self
+    }
+
+    /// Enables progressive widening, which only adds a child to a node once `constant * visits^exponent` exceeds its number of children,
+    /// for games with too many legal actions to try them all. Requires `ExpansionStrategy::Incremental`.
+    pub fn with_progressive_widening(mut self, widening: ProgressiveWidening) -> Self {
+        self.config.progressive_widening = Some(widening);
+        self
+    }
+
+    /// Enables double progressive widening, which widens both the actions of each node and the outcomes of random events,
+    /// for games with too many legal actions or chance outcomes to try them all. Requires `ExpansionStrategy::Incremental`.
+    pub fn with_double_progressive_widening(mut self, action_widening: ProgressiveWidening, outcome_widening: ProgressiveWidening) -> Self {
+        self.config.progressive_widening = Some(action_widening);
+        self.config.outcome_widening = Some(outcome_widening);
+        self
+    }
+
+    /// Enables MCTS-Solver, which proves wins, losses and draws from terminal states and propagates them up the tree.
+    /// Proven nodes are no longer sampled, children proven to lose are never selected, and proven wins are always played.
+    /// Terminal rewards are read as zero-sum: positive for a win, negative for a loss and zero for a draw.
+    /// Games scored otherwise, e.g. 0 for a loss, 0.5 for a draw and 1 for a win, must shift their rewards,
+    /// or their draws count as proven wins. Use `with_score_bounds` for games with other multi-valued outcomes.
+    pub fn with_solver(mut self) -> Self {
+        self.config.solver = true;
+        self
+    }
+
+    /// Enables score-bounded MCTS, for games whose rewards take more values than a win, a loss or a draw.
+    /// Every node keeps bounds on its reward derived from terminal states, children that can't change the reward of their parent
+    /// are never selected, and the search stops early once the bounds of the root meet.
+    pub fn with_score_bounds(mut self) -> Self {
+        self.config.score_bounds = true;
+        self
+    }
+
+    /// Sets how `get_best_action` chooses the action to play. Defaults to `FinalSelection::MaxVisits`.
+    pub fn with_final_selection(mut self, final_selection: FinalSelection) -> Self {
+        self.config.final_selection = final_selection;
+        self
+    }
+
+    /// Caps the number of nodes in the search tree, which is then pruned or stops expanding when it reaches the limit.
+    /// Parallel searches only apply the limit once they finish, and don't support stopping expansion.
+    pub fn with_node_limit(mut self, node_limit: NodeLimit) -> Self {
+        self.node_limit = Some(node_limit);
+        self.enforce_node_limit();
+        self
+    }
+
+    /// Caps the memory used by the nodes of the search tree to roughly the given number of bytes,
+    /// pruning the tree back to three quarters of that whenever it reaches it.
+    /// Only the nodes themselves are counted, not any memory they point to, such as max^n rewards or transpositions.
+    /// The limit is at least two nodes, so that the tree can still be pruned back to its root.
+    pub fn with_memory_limit(self, max_bytes: usize) -> Self {
+        let max_nodes = (max_bytes / std::mem::size_of::<SearchNode<A, Pl>>()).clamp(2, u32::MAX as usize) as u32;
+        self.with_node_limit(NodeLimit::prune(max_nodes))
+    }
+
     /// Runs the MCTS algorithm for the given number of iterations.
+    /// Stops early if the value of the root game state has been proven.
     pub fn run(&mut self, iterations: usize) {
         for _ in 0..iterations {
-            self.root.run_iteration(&mut self.root_game_state.clone(), &self.policy);
+            if self.root.proven_value.is_some() {
+                break;
+            }
+            self.run_iteration();
         }
     }
 
-    /// Returns the best action according to the MCTS algorithm.
+    /// Runs the MCTS algorithm until any of the given limits is reached, or the value of the root game state has been proven.
+    /// Returns the number of iterations that were run.
+    pub fn run_until(&mut self, limit: SearchLimit) -> usize {
+        let mut tracker = LimitTracker::start(limit);
+        while self.root.proven_value.is_none() && tracker.next_iteration(self.node_count) {
+            self.run_iteration();
+        }
+        tracker.get_iterations()
+    }
+
+    /// Runs a single iteration of the MCTS algorithm from the root game state, then enforces the node limit.
+    fn run_iteration(&mut self) {
+        let context = SearchContext {
+            tree_policy: &self.policy,
+            rollout_policy: &self.rollout_policy,
+            prior_provider: &self.prior_provider,
+            config: &self.config,
+            players: &self.players,
+            can_expand: !self.node_limit_stats.is_expansion_stopped
+        };
+        self.node_count += run_root_iteration(&mut self.root, &self.root_game_state, &context, self.transpositions.as_mut(), &mut self.rng);
+        self.enforce_node_limit();
+    }
+
+    /// Prunes the tree or stops expanding it if it has reached its node limit, depending on the limit's strategy.
+    fn enforce_node_limit(&mut self) {
+        let limit = match self.node_limit {
+            Some(limit) if self.node_count >= limit.max_nodes => limit,
+            _ => {
+                self.node_limit_stats.is_expansion_stopped = false;
+                return;
+            }
+        };
+        match limit.strategy {
+            NodeLimitStrategy::Prune { target_nodes } => self.prune(target_nodes),
+            NodeLimitStrategy::StopExpanding => self.node_limit_stats.is_expansion_stopped = true
+        }
+    }
+
+    /// Prunes the subtrees of the least visited nodes until the tree has at most the given number of nodes.
+    /// Every node with more visits than the pruned ones is kept, so ties may leave the tree somewhat smaller.
+    fn prune(&mut self, target_nodes: u32) {
+        let mut visits = Vec::with_capacity(self.node_count as usize);
+        let mut stack: Vec<&SearchNode<A, Pl>> = self.root.children.iter().collect();
+        while let Some(node) = stack.pop() {
+            visits.push(node.visits);
+            stack.extend(&node.children);
+        }
+        let kept_nodes = target_nodes.saturating_sub(1) as usize;
+        if visits.len() <= kept_nodes {
+            return;
+        }
+        let (_, &mut max_pruned_visits, _) = visits.select_nth_unstable_by(kept_nodes, |a, b| b.cmp(a));
+        let removed_nodes = self.root.prune(max_pruned_visits);
+        self.node_count -= removed_nodes;
+        self.node_limit_stats.prunes += 1;
+        self.node_limit_stats.reclaimed_nodes += removed_nodes as u64;
+    }
+
+    /// Advances the root of the search tree by playing the given action, keeping the statistics of the matching child.
+    /// Values in the kept subtree remain relative to the player whose turn it was at the previous root.
+    /// If the action was never expanded, the search starts over from a fresh root.
+    pub fn advance(&mut self, action: &A) {
+        let root_player = self.root.root_player;
+        self.root_game_state.apply_action(action);
+        self.root = match self.root.children.iter().position(|child| child.action == Some(*action)) {
+            Some(index) => {
+                let mut child = self.root.children.swap_remove(index);
+                child.action = None;
+                child
+            },
+            None => SearchNode::new(None, self.root_game_state.get_turn())
+        };
+        self.node_count = self.root.get_node_count();
+        self.enforce_node_limit();
+        // Shared statistics are values for the root player, so they can only be kept if the root player is unchanged.
+        if let Some(table) = self.transpositions.as_mut() {
+            if self.root.root_player != root_player {
+                table.clear();
+            }
+        }
+    }
+
+    /// Advances the root of the search tree by playing the given action followed by the opponent's reply.
+    pub fn advance_with_reply(&mut self, action: &A, reply: &A) {
+        self.advance(action);
+        self.advance(reply);
+    }
+
+    /// Returns the number of nodes in the search tree.
+    pub fn get_node_count(&self) -> u32 {
+        self.node_count
+    }
+
+    /// Returns the statistics of the node limit: how many times the tree was pruned and how many nodes that reclaimed,
+    /// and whether it has stopped expanding.
+    pub fn get_node_limit_stats(&self) -> NodeLimitStats {
+        self.node_limit_stats
+    }
+
+    /// Returns a summary of the search: the statistics of each root action,
+    /// and the principal variation (most visited path) of at most the given depth.
+    pub fn get_report(&self, depth: usize) -> SearchReport<A> {
+        SearchReport::new(&self.root, self.get_mover_sign(), depth)
+    }
+
+    /// Returns a display of the search tree printed with the given options, e.g. limited in depth or to the most visited children.
+    /// The `Display` implementation prints every node instead.
+    pub fn display(&self, options: FormatOptions) -> TreeDisplay<'_, A, Pl> {
+        TreeDisplay::new(&self.root, options)
+    }
+
+    /// Exports the search tree as a Graphviz DOT digraph, keeping the nodes allowed by the given options.
+    /// Nodes are coloured by state, and edges are weighted by their share of the root's visits.
+    pub fn to_dot(&self, options: ExportOptions) -> String {
+        to_dot(&self.root, options)
+    }
+
+    /// Exports the search tree as nested JSON objects, keeping the nodes allowed by the given options.
+    pub fn to_json(&self, options: ExportOptions) -> String {
+        to_json(&self.root, options)
+    }
+
+    /// Returns the proven reward of the root game state for the root player, if MCTS-Solver has proven it.
+    pub fn get_proven_value(&self) -> Option<f32> {
+        self.root.proven_value
+    }
+
+    /// Returns the pessimistic and optimistic bounds on the reward of the root game state for the root player,
+    /// as found by score-bounded MCTS. Unbounded until terminal states have been reached.
+    pub fn get_score_bounds(&self) -> (f32, f32) {
+        (self.root.pessimistic_bound, self.root.optimistic_bound)
+    }
+
+    /// Returns the sign that turns the values of the root's children into rewards for the player to move at the root.
+    /// Values are for the root player in paranoid search, but already for the player who chose each child in max^n search.
+    fn get_mover_sign(&self) -> f32 {
+        match self.players.is_empty() && self.root_game_state.get_turn() != self.root.root_player {
+            true => -1.0,
+            false => 1.0
+        }
+    }
+
+    /// Returns the best action according to the MCTS algorithm, chosen with the final selection strategy.
+    /// With MCTS-Solver, this is a proven win if there is one, and never a proven loss unless every action is one.
+    /// With score-bounded MCTS, this is the proven action with the best score once the bounds of the root meet.
     pub fn get_best_action(&mut self) -> Option<A> {
-        self.root.children.as_slice().into_iter().reduce(|a, b| if a.visits > b.visits { a } else { b }).map(|n| n.action.expect("Expected node to have action"))
+        let sign = self.get_mover_sign();
+        if self.config.solver {
+            if let Some(winning_child) = self.root.children.iter().find(|child| child.proven_value.is_some_and(|value| sign*value > 0.0)) {
+                return winning_child.action;
+            }
+        }
+        if self.config.score_bounds && self.root.proven_value.is_some() {
+            let best_proven_child = self.root.children.iter()
+                .filter(|child| child.proven_value.is_some())
+                .max_by(|a, b| (sign*a.pessimistic_bound).total_cmp(&(sign*b.pessimistic_bound)));
+            if let Some(best_proven_child) = best_proven_child {
+                return best_proven_child.action;
+            }
+        }
+        let (pessimistic_bound, optimistic_bound) = (sign*self.root.pessimistic_bound, sign*self.root.optimistic_bound);
+        let guaranteed_bound = pessimistic_bound.min(optimistic_bound);
+        let is_lost = |child: &&SearchNode<A, Pl>| {
+            child.proven_value.is_some_and(|value| sign*value < 0.0) || (sign*child.pessimistic_bound).max(sign*child.optimistic_bound) < guaranteed_bound
+        };
+        let candidates = match self.root.children.iter().all(|child| is_lost(&child)) {
+            true => self.root.children.iter().collect::<Vec<_>>(),
+            false => self.root.children.iter().filter(|child| !is_lost(child)).collect()
+        };
+        self.config.final_selection.select(candidates, sign, &mut self.rng).map(|n| n.action.expect("Expected node to have action"))
+    }
+}
+
+impl<S, A, Pl, Po, Ro, Pr> SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl> + Hash + Eq, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+    /// Enables transposition search, where nodes that reach the same game state through different move orders share their statistics.
+    /// At most the given number of game states are tracked; nodes reaching other game states keep their own statistics.
+    pub fn with_transpositions(mut self, capacity: usize) -> Self {
+        assert_eq!(self.root.visits, 0, "Transpositions must be enabled before searching");
+        self.transpositions = Some(TranspositionTable::new(capacity));
+        self
+    }
+
+    /// Returns the number of game states tracked by the transposition table, or 0 if transpositions are disabled.
+    pub fn get_transposition_count(&self) -> usize {
+        self.transpositions.as_ref().map_or(0, |table| table.len())
+    }
+}
+
+impl<S, A, Pl, Po, Ro, Pr> SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl> + Send, A: GameAction + Send, Pl: Player + Send + Sync, Po: TreePolicy<A, Pl> + Sync, Ro: RolloutPolicy<S, A, Pl> + Sync, Pr: PriorProvider<S, A, Pl> + Sync {
+    /// Runs the MCTS algorithm for the given number of iterations, split across the given number of threads.
+    /// Each thread searches its own tree from the root game state (root parallelization),
+    /// and the resulting trees are merged into this one once all threads are done.
+    pub fn run_parallel(&mut self, iterations: usize, threads: usize) {
+        assert!(threads > 0, "Thread count must be positive");
+        assert!(self.transpositions.is_none(), "Root-parallel search doesn't support transpositions");
+        assert!(!self.node_limit.is_some_and(|limit| limit.strategy == NodeLimitStrategy::StopExpanding), "Root-parallel search doesn't support stopping expansion at a node limit");
+        let context = SearchContext {
+            tree_policy: &self.policy,
+            rollout_policy: &self.rollout_policy,
+            prior_provider: &self.prior_provider,
+            config: &self.config,
+            players: &self.players,
+            can_expand: true
+        };
+        let context = &context;
+        let root_player = self.root.root_player;
+        let roots: Vec<SearchNode<A, Pl>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|i| {
+                let thread_iterations = iterations / threads + usize::from(i < iterations % threads);
+                let root_game_state = self.root_game_state.clone();
+                let mut rng = self.rng.fork();
+                scope.spawn(move || {
+                    let mut root = SearchNode::new(None, root_player);
+                    for _ in 0..thread_iterations {
+                        run_root_iteration(&mut root, &root_game_state, context, None, &mut rng);
+                    }
+                    root
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().expect("Search thread panicked")).collect()
+        });
+        for root in roots {
+            self.root.merge(root, &self.root_game_state);
+        }
+        self.node_count = self.root.get_node_count();
+        self.enforce_node_limit();
+    }
+}
+
+impl<S, A, Pl, Po, Ro, Pr> SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl> + Send, A: GameAction + Send + Sync, Pl: Player + Send + Sync, Po: TreePolicy<A, Pl> + SharedTreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl> + Sync, Pr: PriorProvider<S, A, Pl> + Sync {
+    /// Runs the MCTS algorithm for the given number of iterations, split across the given number of threads.
+    /// All threads descend the same tree concurrently (tree parallelization), using the given virtual loss
+    /// for every pending visit so that they explore different lines.
+    pub fn run_tree_parallel(&mut self, iterations: usize, threads: usize, virtual_loss: f32) {
+        assert!(threads > 0, "Thread count must be positive");
+        assert!(virtual_loss >= 0.0, "Virtual loss must be non-negative");
+        assert!(self.players.is_empty(), "Tree-parallel search doesn't support max^n search");
+        assert!(!self.config.information_set_search, "Tree-parallel search doesn't support information set search");
+        assert!(self.transpositions.is_none(), "Tree-parallel search doesn't support transpositions");
+        assert!(!self.config.solver && !self.config.score_bounds, "Tree-parallel search doesn't support MCTS-Solver or score bounds");
+        assert!(self.config.progressive_widening.is_none() && self.config.outcome_widening.is_none(), "Tree-parallel search doesn't support progressive widening");
+        assert!(!self.node_limit.is_some_and(|limit| limit.strategy == NodeLimitStrategy::StopExpanding), "Tree-parallel search doesn't support stopping expansion at a node limit");
+        let root_player = self.root.root_player;
+        let root = SharedSearchNode::from(std::mem::replace(&mut self.root, SearchNode::new(None, root_player)));
+        let started_iterations = AtomicUsize::new(0);
+        let context = SearchContext {
+            tree_policy: &self.policy,
+            rollout_policy: &self.rollout_policy,
+            prior_provider: &self.prior_provider,
+            config: &self.config,
+            players: &self.players,
+            can_expand: true
+        };
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                let root_game_state = self.root_game_state.clone();
+                let mut rng = self.rng.fork();
+                let (root, context, started_iterations) = (&root, &context, &started_iterations);
+                scope.spawn(move || {
+                    while started_iterations.fetch_add(1, Ordering::Relaxed) < iterations {
+                        root.run_iteration(&mut root_game_state.clone(), context, virtual_loss, &mut rng);
+                    }
+                });
+            }
+        });
+        self.root = root.into();
+        self.node_count = self.root.get_node_count();
+        self.enforce_node_limit();
+    }
+}
+
+/// Runs a single iteration from the given root node, using max^n search if any players are given.
+/// In information set search, the iteration starts from a determinization of the root game state.
+/// Returns the number of nodes the iteration added.
+fn run_root_iteration<S, A, Pl, Po, Ro, Pr>(root: &mut SearchNode<A, Pl>, root_game_state: &S, context: &SearchContext<Pl, Po, Ro, Pr>, transpositions: Option<&mut TranspositionTable<S>>, rng: &mut Rng) -> u32 where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+    assert!(context.config.progressive_widening.is_none() || context.config.expansion == ExpansionStrategy::Incremental, "Progressive widening doesn't support expanding all children");
+    let mut game = match context.config.information_set_search {
+        true => root_game_state.determinize(root_game_state.get_turn(), rng),
+        false => root_game_state.clone()
+    };
+    if let Some(table) = transpositions {
+        assert!(context.players.is_empty() && !context.config.information_set_search && !context.config.solver && !context.config.score_bounds, "Transposition search doesn't support max^n, information set search, MCTS-Solver or score bounds");
+        root.run_counted_transposition_iteration(&mut game, context, table, rng).1
+    }
+    else if context.players.is_empty() {
+        assert!(!(context.config.solver || context.config.score_bounds) || !context.config.information_set_search, "MCTS-Solver and score bounds don't support information set search");
+        root.run_counted_iteration(&mut game, context, rng).1
+    }
+    else {
+        assert!(!context.config.solver && !context.config.score_bounds, "MCTS-Solver and score bounds don't support max^n search");
+        let root_player_index = context.players.iter().position(|&player| player == root.root_player).expect("Expected root player to be one of the max^n players");
+        let (rewards, added_nodes) = root.run_counted_max_n_iteration(&mut game, context, rng);
+        root.total_value += rewards[root_player_index];
+        root.squared_value += rewards[root_player_index].powi(2);
+        added_nodes
     }
 }
 
-impl<S, A, Pl, Po> Display for SearchTree<S, A, Pl, Po> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl> {
+impl<S, A, Pl, Po, Ro, Pr> Display for SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl>, A: GameAction, Pl: Player, Po: TreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Node count: {}\n{}", self.root.get_node_count(), self.root)
+        writeln!(f, "Node count: {}\n{}", self.get_node_count(), self.root)
     }
-}
\ No newline at end of file
+}
+
+/// The version of the serialized format of search trees, which is bumped whenever the format changes.
+#[cfg(feature = "serde")]
+pub const SEARCH_TREE_FORMAT_VERSION: u32 = 2;
+
+/// The serialized form of a search tree, borrowed from the tree when saving it.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SearchTreeRef<'a, S, A, Pl, Po, Ro, Pr> where A: GameAction, Pl: Player {
+    version: u32,
+    root: &'a SearchNode<A, Pl>,
+    root_game_state: &'a S,
+    policy: &'a Po,
+    rollout_policy: &'a Ro,
+    prior_provider: &'a Pr,
+    config: &'a SearchConfig,
+    players: &'a [Pl]
+}
+
+/// The serialized form of a search tree, owned when loading it.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SearchTreeData<S, A, Pl, Po, Ro, Pr> where A: GameAction, Pl: Player {
+    version: u32,
+    root: SearchNode<A, Pl>,
+    root_game_state: S,
+    policy: Po,
+    rollout_policy: Ro,
+    prior_provider: Pr,
+    config: SearchConfig,
+    players: Vec<Pl>
+}
+
+/// Saves the tree, its root game state, its policies and its settings, tagged with `SEARCH_TREE_FORMAT_VERSION`.
+/// The random number generator, the transposition table and the node limit are not saved.
+#[cfg(feature = "serde")]
+impl<S, A, Pl, Po, Ro, Pr> serde::Serialize for SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl> + serde::Serialize, A: GameAction + serde::Serialize, Pl: Player + serde::Serialize, Po: TreePolicy<A, Pl> + serde::Serialize, Ro: RolloutPolicy<S, A, Pl> + serde::Serialize, Pr: PriorProvider<S, A, Pl> + serde::Serialize {
+    fn serialize<Se>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> where Se: serde::Serializer {
+        SearchTreeRef {
+            version: SEARCH_TREE_FORMAT_VERSION,
+            root: &self.root,
+            root_game_state: &self.root_game_state,
+            policy: &self.policy,
+            rollout_policy: &self.rollout_policy,
+            prior_provider: &self.prior_provider,
+            config: &self.config,
+            players: &self.players
+        }.serialize(serializer)
+    }
+}
+
+/// Loads a tree saved in the current format version, with a freshly seeded random number generator,
+/// no transposition table and no node limit.
+#[cfg(feature = "serde")]
+impl<'de, S, A, Pl, Po, Ro, Pr> serde::Deserialize<'de> for SearchTree<S, A, Pl, Po, Ro, Pr> where S: GameState<A, Pl> + serde::Deserialize<'de>, A: GameAction + serde::Deserialize<'de>, Pl: Player + serde::Deserialize<'de>, Po: TreePolicy<A, Pl> + serde::Deserialize<'de>, Ro: RolloutPolicy<S, A, Pl> + serde::Deserialize<'de>, Pr: PriorProvider<S, A, Pl> + serde::Deserialize<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+        let data = SearchTreeData::<S, A, Pl, Po, Ro, Pr>::deserialize(deserializer)?;
+        if data.version != SEARCH_TREE_FORMAT_VERSION {
+            return Err(serde::de::Error::custom(format!("Unsupported search tree format version {} (expected {})", data.version, SEARCH_TREE_FORMAT_VERSION)));
+        }
+        Ok(SearchTree {
+            node_count: data.root.get_node_count(),
+            root: data.root,
+            root_game_state: data.root_game_state,
+            policy: data.policy,
+            rollout_policy: data.rollout_policy,
+            prior_provider: data.prior_provider,
+            config: data.config,
+            rng: Rng::new(),
+            players: data.players,
+            transpositions: None,
+            node_limit: None,
+            node_limit_stats: NodeLimitStats::default()
+        })
+    }
+}
@@ -0,0 +1,326 @@
+//! Contains the SharedSearchNode struct, a thread-safe search node used by tree-parallel search.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+use fastrand::Rng;
+use crate::game::{choose_untried_action, sample_chance_outcome, GameAction, GameState, Player};
+use crate::rollout_policy::{simulate, RolloutPolicy};
+use crate::prior_provider::PriorProvider;
+use crate::search_config::{ExpansionStrategy, SearchContext};
+use crate::search_node::{NodeState, SearchNode};
+use crate::tree_policy::SharedTreePolicy;
+
+/// Represents a node in a search tree that is shared between multiple threads.
+/// Statistics are kept in atomic counters, and the children are guarded by a read-write lock, only locked for writing to expand.
+pub struct SharedSearchNode<A, Pl> where A: GameAction, Pl: Player {
+    /// The action that this node represents. Only None for the root node.
+    pub action: Option<A>,
+    /// The player whose turn it was at the root node (initial game position).
+    pub root_player: Pl,
+    /// The state of this node and its children.
+    pub expansion: RwLock<SharedExpansion<A, Pl>>,
+    /// The number of times this node has been visited.
+    pub visits: AtomicU32,
+    /// The number of threads currently descending through this node, whose visits are still pending.
+    pub virtual_visits: AtomicU32,
+    /// The prior probability of this node's action, as given by the prior provider when the node was added.
+    pub prior: f32,
+    /// The bits of the total value of this node as a result of rollouts, stored as a f32.
+    total_value_bits: AtomicU32,
+    /// The bits of the total of the squared rewards of this node, stored as a f32.
+    squared_value_bits: AtomicU32
+}
+
+/// The state and children of a SharedSearchNode, which change together when the node is expanded.
+pub struct SharedExpansion<A, Pl> where A: GameAction, Pl: Player {
+    /// The state of the node.
+    pub state: NodeState,
+    /// The children of the node.
+    pub children: Vec<Arc<SharedSearchNode<A, Pl>>>
+}
+
+impl<A, Pl> SharedSearchNode<A, Pl> where A: GameAction, Pl: Player {
+    /// Constructs a new shared search node with the given action and root_player.
+    pub fn new(action: Option<A>, root_player: Pl) -> SharedSearchNode<A, Pl> {
+        SharedSearchNode {
+            action,
+            root_player,
+            expansion: RwLock::new(SharedExpansion {
+                state: NodeState::ExpandableLeaf,
+                children: Vec::new()
+            }),
+            visits: AtomicU32::new(0),
+            virtual_visits: AtomicU32::new(0),
+            prior: 1.0,
+            total_value_bits: AtomicU32::new(0.0f32.to_bits()),
+            squared_value_bits: AtomicU32::new(0.0f32.to_bits())
+        }
+    }
+
+    /// Returns the total value of this node as a result of rollouts.
+    pub fn total_value(&self) -> f32 {
+        f32::from_bits(self.total_value_bits.load(Ordering::Relaxed))
+    }
+
+    /// Returns the total of the squared rewards of this node.
+    pub fn squared_value(&self) -> f32 {
+        f32::from_bits(self.squared_value_bits.load(Ordering::Relaxed))
+    }
+
+    /// Records a visit to this node with the given reward.
+    fn record(&self, reward: f32) {
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        self.total_value_bits.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f32::from_bits(bits) + reward).to_bits())
+        }).expect("Expected total value update to succeed");
+        self.squared_value_bits.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f32::from_bits(bits) + reward.powi(2)).to_bits())
+        }).expect("Expected squared value update to succeed");
+    }
+
+    /// Runs a single iteration of the MCTS algorithm. May be called from multiple threads at once.
+    /// Children are selected with the given virtual loss applied for every pending visit, so that concurrent threads diversify.
+    /// The selected path is kept on a stack rather than recursed through, and its nodes are visited as the reward is backed up.
+    /// Returns the reward for the player whose turn it was at the root node (initial game position).
+    pub fn run_iteration<S, Po, Ro, Pr>(&self, game: &mut S, context: &SearchContext<Pl, Po, Ro, Pr>, virtual_loss: f32, rng: &mut Rng) -> f32 where S: GameState<A, Pl>, Po: SharedTreePolicy<A, Pl>, Ro: RolloutPolicy<S, A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        // The descendants of this node on the selected path, and whether a virtual visit is pending on each of them.
+        let mut path: Vec<(Arc<SharedSearchNode<A, Pl>>, bool)> = Vec::new();
+        let delta = loop {
+            let node = path.last().map_or(self, |(node, _)| node.as_ref());
+            // Children are selected under a read lock, so that threads only wait for each other to add children.
+            let expansion = node.expansion.read().expect("Node lock poisoned");
+            let mut sampled_outcome = None;
+            let selected = match expansion.state {
+                NodeState::Expanded => {
+                    let parent_visits = node.visits.load(Ordering::Relaxed) + node.virtual_visits.load(Ordering::Relaxed);
+                    let child = Arc::clone(context.tree_policy.select_shared_child(parent_visits, &expansion.children, game.get_turn() == node.root_player, virtual_loss));
+                    child.virtual_visits.fetch_add(1, Ordering::Relaxed);
+                    Some((child, true))
+                },
+                NodeState::Chance => {
+                    let outcomes = game.get_chance_outcomes().expect("Expected chance node to have outcomes");
+                    let (outcome, probability) = sample_chance_outcome(&outcomes, rng);
+                    sampled_outcome = Some((outcome, probability));
+                    expansion.children.iter().find(|child| child.action == Some(outcome)).map(|child| (Arc::clone(child), false))
+                },
+                NodeState::TerminalLeaf => break game.get_reward_for_player(node.root_player),
+                NodeState::ExpandableLeaf => None
+            };
+            drop(expansion);
+            if let Some((child, is_virtual)) = selected {
+                game.apply_action(&child.action.expect("Expected child node to have action"));
+                path.push((child, is_virtual));
+                continue;
+            }
+            let mut expansion = node.expansion.write().expect("Node lock poisoned");
+            if expansion.state == NodeState::ExpandableLeaf && expansion.children.is_empty() && game.get_chance_outcomes().is_some() {
+                expansion.state = NodeState::Chance;
+            }
+            let (child, is_virtual) = match expansion.state {
+                NodeState::ExpandableLeaf if context.config.expansion == ExpansionStrategy::All => {
+                    match Self::expand_all(&mut expansion, game, node.root_player, context.prior_provider) {
+                        true => {
+                            drop(expansion);
+                            break simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                        },
+                        false => break game.get_reward_for_player(node.root_player)
+                    }
+                },
+                NodeState::ExpandableLeaf => {
+                    match Self::expand(&mut expansion, game, node.root_player, context.prior_provider, rng) {
+                        Some(child) => {
+                            drop(expansion);
+                            game.apply_action(&child.action.expect("Expected child node to have action"));
+                            let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                            child.record(reward);
+                            break reward;
+                        },
+                        None => break game.get_reward_for_player(node.root_player)
+                    }
+                },
+                NodeState::TerminalLeaf => {
+                    break game.get_reward_for_player(node.root_player);
+                },
+                // Another thread expanded this node since it was read, so its children are selected under a read lock again.
+                NodeState::Expanded => continue,
+                NodeState::Chance => {
+                    let (outcome, probability) = sampled_outcome.unwrap_or_else(|| {
+                        let outcomes = game.get_chance_outcomes().expect("Expected chance node to have outcomes");
+                        sample_chance_outcome(&outcomes, rng)
+                    });
+                    match expansion.children.iter().find(|child| child.action == Some(outcome)) {
+                        Some(child) => (Arc::clone(child), false),
+                        None => {
+                            let mut child = SharedSearchNode::new(Some(outcome), node.root_player);
+                            child.prior = probability;
+                            let child = Arc::new(child);
+                            expansion.children.push(Arc::clone(&child));
+                            drop(expansion);
+                            game.apply_action(&outcome);
+                            let reward = simulate(game, context.rollout_policy, context.config, node.root_player, rng);
+                            child.record(reward);
+                            break reward;
+                        }
+                    }
+                }
+            };
+            drop(expansion);
+            game.apply_action(&child.action.expect("Expected child node to have action"));
+            path.push((child, is_virtual));
+        };
+        for (node, is_virtual) in path.iter().rev() {
+            node.record(delta);
+            if *is_virtual {
+                node.virtual_visits.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        self.record(delta);
+        delta
+    }
+
+    /// Adds a child node using an untried legal action, like SearchNode::expand.
+    fn expand<S, Pr>(expansion: &mut SharedExpansion<A, Pl>, game: &S, root_player: Pl, prior_provider: &Pr, rng: &mut Rng) -> Option<Arc<SharedSearchNode<A, Pl>>> where S: GameState<A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let allowed_actions = game.get_actions();
+        if allowed_actions.is_empty() {
+            expansion.state = NodeState::TerminalLeaf;
+            return None;
+        }
+        let candidate_actions: Vec<A> = allowed_actions.iter()
+            .filter(|action| !expansion.children.iter().any(|child| child.action == Some(**action)))
+            .copied()
+            .collect();
+        assert!(!candidate_actions.is_empty(), "Expected at least one candidate action");
+        if candidate_actions.len() == 1 {
+            expansion.state = NodeState::Expanded;
+        }
+        let action = choose_untried_action(game, &candidate_actions, |action| !expansion.children.iter().any(|child| child.action == Some(*action)), rng);
+        let priors = prior_provider.get_priors(game, &allowed_actions);
+        let mut child = SharedSearchNode::new(Some(action), root_player);
+        child.prior = priors[allowed_actions.iter().position(|allowed| *allowed == action).expect("Expected child action to be legal")];
+        let child = Arc::new(child);
+        expansion.children.push(Arc::clone(&child));
+        Some(child)
+    }
+
+    /// Adds a child node for every legal action at once, like SearchNode::expand_all.
+    fn expand_all<S, Pr>(expansion: &mut SharedExpansion<A, Pl>, game: &S, root_player: Pl, prior_provider: &Pr) -> bool where S: GameState<A, Pl>, Pr: PriorProvider<S, A, Pl> {
+        let allowed_actions = game.get_actions();
+        if allowed_actions.is_empty() {
+            expansion.state = NodeState::TerminalLeaf;
+            return false;
+        }
+        let priors = prior_provider.get_priors(game, &allowed_actions);
+        for (&action, &prior) in allowed_actions.iter().zip(&priors) {
+            if expansion.children.iter().any(|child| child.action == Some(action)) {
+                continue;
+            }
+            let mut child = SharedSearchNode::new(Some(action), root_player);
+            child.prior = prior;
+            expansion.children.push(Arc::new(child));
+        }
+        expansion.state = NodeState::Expanded;
+        true
+    }
+
+    /// Converts a search node whose children have been taken out, giving it the given converted children instead.
+    fn from_childless(node: SearchNode<A, Pl>, children: Vec<Arc<SharedSearchNode<A, Pl>>>) -> Self {
+        SharedSearchNode {
+            action: node.action,
+            root_player: node.root_player,
+            expansion: RwLock::new(SharedExpansion {
+                state: node.state,
+                children
+            }),
+            visits: AtomicU32::new(node.visits),
+            virtual_visits: AtomicU32::new(0),
+            prior: node.prior,
+            total_value_bits: AtomicU32::new(node.total_value.to_bits()),
+            squared_value_bits: AtomicU32::new(node.squared_value.to_bits())
+        }
+    }
+
+    /// Takes the children out of this node, even if its mutex was poisoned.
+    fn take_children(&mut self) -> Vec<Arc<SharedSearchNode<A, Pl>>> {
+        let expansion = self.expansion.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::take(&mut expansion.children)
+    }
+}
+
+impl<A, Pl> From<SearchNode<A, Pl>> for SharedSearchNode<A, Pl> where A: GameAction, Pl: Player {
+    /// Converts the tree without recursing: each node waits on a stack, with its remaining children and those already
+    /// converted, until all its children have been converted.
+    fn from(mut root: SearchNode<A, Pl>) -> Self {
+        let children = std::mem::take(&mut root.children).into_iter();
+        let mut stack = vec![(root, children, Vec::new())];
+        loop {
+            let (_, children, _) = stack.last_mut().expect("Expected conversion stack to be non-empty");
+            match children.next() {
+                Some(mut child) => {
+                    let grandchildren = std::mem::take(&mut child.children).into_iter();
+                    stack.push((child, grandchildren, Vec::new()));
+                },
+                None => {
+                    let (node, _, children) = stack.pop().expect("Expected conversion stack to be non-empty");
+                    let shared = SharedSearchNode::from_childless(node, children);
+                    match stack.last_mut() {
+                        Some((_, _, siblings)) => siblings.push(Arc::new(shared)),
+                        None => return shared
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<A, Pl> From<SharedSearchNode<A, Pl>> for SearchNode<A, Pl> where A: GameAction, Pl: Player {
+    /// Converts the tree without recursing, like the conversion from SearchNode.
+    fn from(mut root: SharedSearchNode<A, Pl>) -> Self {
+        let children = root.take_children().into_iter();
+        let mut stack = vec![(root, children, Vec::new())];
+        loop {
+            let (_, children, _) = stack.last_mut().expect("Expected conversion stack to be non-empty");
+            match children.next() {
+                Some(child) => {
+                    let mut child = Arc::into_inner(child).expect("Expected shared node to have no other owners");
+                    let grandchildren = child.take_children().into_iter();
+                    stack.push((child, grandchildren, Vec::new()));
+                },
+                None => {
+                    let (mut shared, _, children) = stack.pop().expect("Expected conversion stack to be non-empty");
+                    let node = SearchNode {
+                        action: shared.action,
+                        children,
+                        root_player: shared.root_player,
+                        state: shared.expansion.get_mut().expect("Node lock poisoned").state,
+                        visits: shared.visits.load(Ordering::Relaxed),
+                        total_value: shared.total_value(),
+                        squared_value: shared.squared_value(),
+                        rewards: Vec::new(),
+                        availability: 0,
+                        prior: shared.prior,
+                        transposition_key: None,
+                        proven_value: None,
+                        pessimistic_bound: f32::NEG_INFINITY,
+                        optimistic_bound: f32::INFINITY
+                    };
+                    match stack.last_mut() {
+                        Some((_, _, siblings)) => siblings.push(node),
+                        None => return node
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<A, Pl> Drop for SharedSearchNode<A, Pl> where A: GameAction, Pl: Player {
+    /// Drops the subtree without recursing, unlinking the children of every node this was the last owner of.
+    fn drop(&mut self) {
+        let mut stack = self.take_children();
+        while let Some(child) = stack.pop() {
+            if let Some(mut child) = Arc::into_inner(child) {
+                stack.append(&mut child.take_children());
+            }
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use crate::tic_tac_toe::{TicTacToeMove, TicTacToePlayer, TicTacToePosition};
-use crate::{SearchTree, UctPolicy};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use crate::{ArenaSearchTree, ExpansionStrategy, ExportOptions, FinalSelection, FormatOptions, GameAction, GameState, NodeLimit, NodeState, Player, PriorProvider, ProgressiveWidening, PuctPolicy, RolloutPolicy, Rng, SearchConfig, SearchContext, SearchLimit, SearchNode, SearchReport, SearchTree, SharedSearchNode, TreeDisplay, UctPolicy, UniformPriorProvider, UniformRolloutPolicy};
 
 #[test]
 fn weak_test() {
     let init_game_state = TicTacToePosition::new();
     for m in init_game_state.get_moves() {
-        let mut game_state = init_game_state.clone();
+        let mut game_state = init_game_state;
         game_state.make_move(m);
         let opt_result = get_opt_result_by_mocats(game_state);
         assert_eq!(opt_result, None);
@@ -13,7 +16,7 @@ fn weak_test() {
 }
 
 fn get_opt_result_by_mocats(pos: TicTacToePosition) -> Option<TicTacToePlayer> {
-    let mut game = pos.clone();
+    let mut game = pos;
     let mut moves = game.get_moves();
     while !moves.is_empty() {
         let mut mcts = SearchTree::<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>::new(game, UctPolicy::new(2.));
@@ -25,3 +28,1035 @@ fn get_opt_result_by_mocats(pos: TicTacToePosition) -> Option<TicTacToePlayer> {
     game.get_winner()
 }
 
+
+#[test]
+fn root_parallel_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.));
+    mcts.run_parallel(4000, 4);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+}
+
+#[test]
+fn tree_parallel_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.));
+    mcts.run_tree_parallel(4000, 4, 1.);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+}
+
+#[test]
+fn search_limit_test() {
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.));
+    assert_eq!(mcts.run_until(SearchLimit::iterations(500)), 500);
+    mcts.run_until(SearchLimit::nodes(1000).with_duration(Duration::from_secs(60)));
+    assert_eq!(mcts.get_node_count(), 1000);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.));
+    assert!(mcts.run_until(SearchLimit::duration(Duration::from_millis(20))) > 0);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_rollout_policy(SlowRolloutPolicy);
+    let iterations = mcts.run_until(SearchLimit::duration(Duration::from_millis(20)));
+    assert!(iterations > 0 && iterations < 20);
+}
+
+/// Plays uniformly random moves, sleeping for a millisecond before each one like a slow evaluation would.
+struct SlowRolloutPolicy;
+
+impl RolloutPolicy<TicTacToePosition, TicTacToeMove, TicTacToePlayer> for SlowRolloutPolicy {
+    fn select_action(&self, _game: &TicTacToePosition, actions: &[TicTacToeMove], rng: &mut Rng) -> TicTacToeMove {
+        std::thread::sleep(Duration::from_millis(1));
+        actions[rng.usize(0..actions.len())]
+    }
+}
+
+#[test]
+fn advance_test() {
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.));
+    mcts.run(5000);
+    let best = mcts.get_best_action().unwrap();
+    let node_count = mcts.get_node_count();
+    mcts.advance(&best);
+    let kept_node_count = mcts.get_node_count();
+    assert!(kept_node_count > 1 && kept_node_count < node_count);
+    mcts.advance(&TicTacToeMove { pos: 1 << (!best.pos & 0b111111111).trailing_zeros() });
+    mcts.run(100);
+    assert!(mcts.get_best_action().is_some());
+}
+
+/// Plays a winning move whenever there is one, and the first legal move otherwise.
+struct WinningMoveRolloutPolicy;
+
+impl RolloutPolicy<TicTacToePosition, TicTacToeMove, TicTacToePlayer> for WinningMoveRolloutPolicy {
+    fn select_action(&self, game: &TicTacToePosition, actions: &[TicTacToeMove], _rng: &mut Rng) -> TicTacToeMove {
+        *actions.iter().find(|action| {
+            let mut next = *game;
+            next.make_move(action.pos);
+            next.get_winner().is_some()
+        }).unwrap_or(&actions[0])
+    }
+}
+
+#[test]
+fn rollout_policy_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_rollout_policy(WinningMoveRolloutPolicy);
+    mcts.run(5);
+    // Rollouts always take a win when there is one, so X loses after any move that neither wins nor blocks O.
+    for action in mcts.get_report(0).actions {
+        let expected_value = match action.action.pos {
+            0b100 => 1.,
+            0b100000 => 0.,
+            _ => -1.
+        };
+        assert_eq!(action.mean_value, expected_value);
+    }
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+}
+
+#[test]
+fn truncated_rollout_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_max_rollout_depth(0);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let mut mcts = SearchTree::new(EvaluatedPosition(TicTacToePosition::new()), UctPolicy::new(2.)).with_max_rollout_depth(0);
+    mcts.run(9);
+    let report = mcts.get_report(0);
+    assert_eq!(report.actions.len(), 9);
+    assert!(report.actions.iter().all(|action| action.mean_value == 0.25));
+}
+
+/// Tic-tac-toe with a distinctive static evaluation of 0.25 for X and -0.25 for O.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EvaluatedPosition(TicTacToePosition);
+
+impl GameState<TicTacToeMove, TicTacToePlayer> for EvaluatedPosition {
+    fn get_actions(&self) -> Vec<TicTacToeMove> {
+        self.0.get_actions()
+    }
+
+    fn apply_action(&mut self, action: &TicTacToeMove) {
+        self.0.apply_action(action);
+    }
+
+    fn get_turn(&self) -> TicTacToePlayer {
+        self.0.get_turn()
+    }
+
+    fn get_reward_for_player(&self, player: TicTacToePlayer) -> f32 {
+        self.0.get_reward_for_player(player)
+    }
+
+    fn evaluate(&self, player: TicTacToePlayer) -> f32 {
+        match player {
+            TicTacToePlayer::X => 0.25,
+            TicTacToePlayer::O => -0.25
+        }
+    }
+}
+
+#[test]
+fn seeded_search_test() {
+    let run_seeded = |seed: u64| {
+        let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_seed(seed);
+        mcts.run(3000);
+        mcts.run_parallel(3000, 3);
+        (mcts.to_string(), mcts.get_best_action())
+    };
+    assert_eq!(run_seeded(42), run_seeded(42));
+    assert_ne!(run_seeded(42).0, run_seeded(7).0);
+}
+
+#[test]
+fn max_n_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_max_n(vec![TicTacToePlayer::X, TicTacToePlayer::O]);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::O
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_max_n(vec![TicTacToePlayer::X, TicTacToePlayer::O]);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100000);
+    assert!(mcts.get_report(0).actions.iter().all(|action| action.mean_value <= 1.));
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_max_n(vec![TicTacToePlayer::X, TicTacToePlayer::O]).with_final_selection(FinalSelection::MaxValue);
+    mcts.run(2000);
+    mcts.advance(&TicTacToeMove { pos: 0b1000000 });
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100000);
+    let report = mcts.get_report(0);
+    assert_eq!(report.actions[0].action.pos, 0b100000);
+    assert!(report.actions[0].mean_value > 0.9);
+}
+
+/// A three-player game of two moves: the first player picks a branch, then the second player (left) or the third (right)
+/// picks one of two outcomes. The player to move on the left can hurt the first player, but only at a cost to itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BranchState {
+    branch: Option<usize>,
+    outcome: Option<usize>
+}
+
+/// The rewards of the first, second and third players for each branch and outcome.
+const BRANCH_REWARDS: [[[f32; 3]; 2]; 2] = [[[0.5, 0.5, 0.], [0.1, 0.4, 0.5]], [[0.3, 0., 0.7], [0.2, 0.6, 0.2]]];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BranchPlayer {
+    First,
+    Second,
+    Third
+}
+
+impl Player for BranchPlayer {}
+
+impl GameState<ScoreTableAction, BranchPlayer> for BranchState {
+    fn get_actions(&self) -> Vec<ScoreTableAction> {
+        match self.outcome {
+            Some(_) => Vec::new(),
+            None => (0..2).map(ScoreTableAction).collect()
+        }
+    }
+
+    fn apply_action(&mut self, action: &ScoreTableAction) {
+        match self.branch {
+            Some(_) => self.outcome = Some(action.0),
+            None => self.branch = Some(action.0)
+        }
+    }
+
+    fn get_turn(&self) -> BranchPlayer {
+        match self.branch {
+            None => BranchPlayer::First,
+            Some(0) => BranchPlayer::Second,
+            Some(_) => BranchPlayer::Third
+        }
+    }
+
+    fn get_reward_for_player(&self, player: BranchPlayer) -> f32 {
+        match (self.branch, self.outcome) {
+            (Some(branch), Some(outcome)) => BRANCH_REWARDS[branch][outcome][player as usize],
+            _ => 0.
+        }
+    }
+}
+
+#[test]
+fn max_n_three_player_test() {
+    let game = BranchState { branch: None, outcome: None };
+    let players = vec![BranchPlayer::First, BranchPlayer::Second, BranchPlayer::Third];
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1).with_max_n(players);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action(), Some(ScoreTableAction(0)));
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action(), Some(ScoreTableAction(1)));
+}
+
+/// A single-player game: take a safe reward of 0.5, or gamble for 1 with the given odds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GambleState {
+    win_probability: f32,
+    is_gambling: bool,
+    reward: Option<f32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GambleAction {
+    Safe,
+    Gamble,
+    Win,
+    Lose
+}
+
+impl GameAction for GambleAction {}
+
+impl Display for GambleAction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Gambler;
+
+impl Player for Gambler {}
+
+impl GameState<GambleAction, Gambler> for GambleState {
+    fn get_actions(&self) -> Vec<GambleAction> {
+        match self.reward.is_none() && !self.is_gambling {
+            true => vec![GambleAction::Safe, GambleAction::Gamble],
+            false => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &GambleAction) {
+        match action {
+            GambleAction::Safe => self.reward = Some(0.5),
+            GambleAction::Gamble => self.is_gambling = true,
+            GambleAction::Win => self.reward = Some(1.),
+            GambleAction::Lose => self.reward = Some(0.)
+        }
+        if self.reward.is_some() {
+            self.is_gambling = false;
+        }
+    }
+
+    fn get_turn(&self) -> Gambler {
+        Gambler
+    }
+
+    fn get_reward_for_player(&self, _player: Gambler) -> f32 {
+        self.reward.unwrap_or(0.)
+    }
+
+    fn get_chance_outcomes(&self) -> Option<Vec<(GambleAction, f32)>> {
+        match self.is_gambling {
+            true => Some(vec![(GambleAction::Win, self.win_probability), (GambleAction::Lose, 1. - self.win_probability)]),
+            false => None
+        }
+    }
+}
+
+#[test]
+fn chance_node_test() {
+    for (win_probability, expected_action) in [(0.2, GambleAction::Safe), (0.8, GambleAction::Gamble)] {
+        let game = GambleState { win_probability, is_gambling: false, reward: None };
+        let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1);
+        mcts.run(2000);
+        assert_eq!(mcts.get_best_action(), Some(expected_action));
+    }
+}
+
+/// A single-player game with hidden information: guess the hidden card for a reward of 1 (or -1 if wrong), or pass for 0.25.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HiddenCardState {
+    card: u8,
+    reward: Option<f32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GuessAction {
+    Guess(u8),
+    Pass
+}
+
+impl GameAction for GuessAction {}
+
+impl Display for GuessAction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl GameState<GuessAction, Gambler> for HiddenCardState {
+    fn get_actions(&self) -> Vec<GuessAction> {
+        match self.reward {
+            None => vec![GuessAction::Guess(0), GuessAction::Guess(1), GuessAction::Pass],
+            Some(_) => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &GuessAction) {
+        self.reward = Some(match action {
+            GuessAction::Guess(card) if *card == self.card => 1.,
+            GuessAction::Guess(_) => -1.,
+            GuessAction::Pass => 0.25
+        });
+    }
+
+    fn get_turn(&self) -> Gambler {
+        Gambler
+    }
+
+    fn get_reward_for_player(&self, _player: Gambler) -> f32 {
+        self.reward.unwrap_or(0.)
+    }
+
+    fn determinize(&self, _observer: Gambler, rng: &mut Rng) -> Self {
+        HiddenCardState { card: rng.u8(0..2), reward: self.reward }
+    }
+}
+
+#[test]
+fn information_set_search_test() {
+    let game = HiddenCardState { card: 1, reward: None };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action(), Some(GuessAction::Guess(1)));
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1).with_information_set_search();
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action(), Some(GuessAction::Pass));
+    let mut root = SearchNode::new(None, Gambler);
+    let context = SearchContext {
+        tree_policy: &UctPolicy::new(0.5),
+        rollout_policy: &UniformRolloutPolicy,
+        prior_provider: &UniformPriorProvider,
+        config: &SearchConfig { information_set_search: true, ..SearchConfig::default() },
+        players: &[],
+        can_expand: true
+    };
+    let mut rng = Rng::with_seed(1);
+    for _ in 0..100 {
+        root.run_iteration(&mut game.determinize(Gambler, &mut rng), &context, &mut rng);
+    }
+    let mut availabilities: Vec<u32> = root.children.iter().map(|child| child.availability).collect();
+    availabilities.sort();
+    assert_eq!(availabilities, vec![98, 99, 100]);
+}
+
+/// A two-player game with hidden information: each player holds a hidden card, and X then O guess the other's card,
+/// scoring 1 for a right guess. Only the player to move may determinize the game, which resamples the opponent's card.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CardDuelState {
+    cards: [u8; 2],
+    guesses: [Option<u8>; 2]
+}
+
+impl GameState<GuessAction, TicTacToePlayer> for CardDuelState {
+    fn get_actions(&self) -> Vec<GuessAction> {
+        match self.guesses[1] {
+            None => vec![GuessAction::Guess(0), GuessAction::Guess(1)],
+            Some(_) => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &GuessAction) {
+        if let GuessAction::Guess(card) = action {
+            let index = usize::from(self.guesses[0].is_some());
+            self.guesses[index] = Some(*card);
+        }
+    }
+
+    fn get_turn(&self) -> TicTacToePlayer {
+        match self.guesses[0] {
+            None => TicTacToePlayer::X,
+            Some(_) => TicTacToePlayer::O
+        }
+    }
+
+    fn get_reward_for_player(&self, player: TicTacToePlayer) -> f32 {
+        let score = |guesser: usize| if self.guesses[guesser] == Some(self.cards[1 - guesser]) { 1. } else { 0. };
+        match player {
+            TicTacToePlayer::X => score(0) - score(1),
+            TicTacToePlayer::O => score(1) - score(0)
+        }
+    }
+
+    fn determinize(&self, observer: TicTacToePlayer, rng: &mut Rng) -> Self {
+        assert_eq!(observer, self.get_turn(), "Expected to determinize for the player to move");
+        let mut state = *self;
+        let hidden = match observer {
+            TicTacToePlayer::X => 1,
+            TicTacToePlayer::O => 0
+        };
+        state.cards[hidden] = rng.u8(0..2);
+        state
+    }
+}
+
+#[test]
+fn information_set_search_advance_test() {
+    let game = CardDuelState { cards: [0, 1], guesses: [None, None] };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.5)).with_seed(1).with_information_set_search();
+    mcts.run(200);
+    mcts.advance(&GuessAction::Guess(1));
+    mcts.run(200);
+    assert!(mcts.get_report(0).visits >= 200);
+}
+
+/// Puts most of the prior on winning moves, and spreads the rest uniformly.
+struct WinningMovePriorProvider;
+
+impl PriorProvider<TicTacToePosition, TicTacToeMove, TicTacToePlayer> for WinningMovePriorProvider {
+    fn get_priors(&self, game: &TicTacToePosition, actions: &[TicTacToeMove]) -> Vec<f32> {
+        let weights: Vec<f32> = actions.iter().map(|action| {
+            let mut next = *game;
+            next.make_move(action.pos);
+            if next.get_winner().is_some() { 10. } else { 1. }
+        }).collect();
+        let total: f32 = weights.iter().sum();
+        weights.iter().map(|weight| weight / total).collect()
+    }
+}
+
+#[test]
+fn puct_policy_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, PuctPolicy::new(2.)).with_seed(1).with_prior_provider(WinningMovePriorProvider);
+    mcts.run(100);
+    let mut uniform_mcts = SearchTree::new(game, PuctPolicy::new(2.)).with_seed(1);
+    uniform_mcts.run(100);
+    let winning_share = |report: SearchReport<TicTacToeMove>| report.actions.iter().find(|action| action.action.pos == 0b100).expect("Expected winning move to be searched").visit_share;
+    assert!(winning_share(mcts.get_report(0)) > winning_share(uniform_mcts.get_report(0)));
+    mcts.run(1900);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let mut mcts = SearchTree::new(game, PuctPolicy::new(2.));
+    mcts.run_tree_parallel(2000, 4, 1.);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+}
+
+#[test]
+fn expand_all_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run(1);
+    assert_eq!(mcts.get_node_count(), 6);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let mut mcts = SearchTree::new(game, PuctPolicy::new(2.))
+        .with_prior_provider(WinningMovePriorProvider)
+        .with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run_tree_parallel(2000, 4, 1.);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run_until(SearchLimit::nodes(1000));
+    assert!(mcts.get_node_count() >= 1000 && mcts.get_node_count() < 1000 + 9);
+}
+
+#[test]
+fn transposition_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_transpositions(10000);
+    mcts.run(2000);
+    assert!(mcts.get_transposition_count() > 0);
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_transpositions(10000);
+    mcts.run(5000);
+    assert!(mcts.get_transposition_count() < mcts.get_node_count() as usize);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_transpositions(50);
+    mcts.run(5000);
+    assert_eq!(mcts.get_transposition_count(), 50);
+    assert!(mcts.get_best_action().is_some());
+}
+
+#[test]
+fn solver_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_solver();
+    mcts.run(5);
+    assert_eq!(mcts.get_proven_value(), Some(1.));
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    let game = TicTacToePosition {
+        board_x: 0b000001011,
+        board_o: 0b100010000,
+        turn: TicTacToePlayer::O
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_solver();
+    mcts.run(500);
+    assert_eq!(mcts.get_proven_value(), Some(-1.));
+    let node_count = mcts.get_node_count();
+    mcts.run(500);
+    assert_eq!(mcts.get_node_count(), node_count);
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_solver().with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run(500);
+    assert_eq!(mcts.get_proven_value(), Some(-1.));
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_solver().with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run(5);
+    assert_eq!(mcts.get_proven_value(), Some(1.));
+    assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+}
+
+/// A two-player game of two moves, where X picks a row and O a column of a score table, scored for X.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScoreTableState {
+    row: Option<usize>,
+    column: Option<usize>
+}
+
+const SCORE_TABLE: [[f32; 3]; 3] = [[3., -1., 2.], [1., 2., 1.], [0., 5., -2.]];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScoreTableAction(usize);
+
+impl GameAction for ScoreTableAction {}
+
+impl Display for ScoreTableAction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl GameState<ScoreTableAction, TicTacToePlayer> for ScoreTableState {
+    fn get_actions(&self) -> Vec<ScoreTableAction> {
+        match self.column {
+            Some(_) => Vec::new(),
+            None => (0..3).map(ScoreTableAction).collect()
+        }
+    }
+
+    fn apply_action(&mut self, action: &ScoreTableAction) {
+        match self.row {
+            Some(_) => self.column = Some(action.0),
+            None => self.row = Some(action.0)
+        }
+    }
+
+    fn get_turn(&self) -> TicTacToePlayer {
+        match self.row {
+            Some(_) => TicTacToePlayer::O,
+            None => TicTacToePlayer::X
+        }
+    }
+
+    fn get_reward_for_player(&self, player: TicTacToePlayer) -> f32 {
+        let score = match (self.row, self.column) {
+            (Some(row), Some(column)) => SCORE_TABLE[row][column],
+            _ => 0.
+        };
+        match player {
+            TicTacToePlayer::X => score,
+            TicTacToePlayer::O => -score
+        }
+    }
+}
+
+/// A two-player game where X either ends the game for a score of 1, or takes a detour where O picks a score from 5 to 9, scored for X.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DetourState {
+    is_detour: Option<bool>,
+    score: Option<f32>
+}
+
+const DETOUR_SCORES: [f32; 5] = [5., 6., 7., 8., 9.];
+
+impl GameState<ScoreTableAction, TicTacToePlayer> for DetourState {
+    fn get_actions(&self) -> Vec<ScoreTableAction> {
+        match (self.is_detour, self.score) {
+            (None, _) => (0..2).map(ScoreTableAction).collect(),
+            (Some(true), None) => (0..DETOUR_SCORES.len()).map(ScoreTableAction).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &ScoreTableAction) {
+        match self.is_detour {
+            Some(_) => self.score = Some(DETOUR_SCORES[action.0]),
+            None => {
+                self.is_detour = Some(action.0 == 0);
+                if action.0 == 1 {
+                    self.score = Some(1.);
+                }
+            }
+        }
+    }
+
+    fn get_turn(&self) -> TicTacToePlayer {
+        match self.is_detour {
+            Some(_) => TicTacToePlayer::O,
+            None => TicTacToePlayer::X
+        }
+    }
+
+    fn get_reward_for_player(&self, player: TicTacToePlayer) -> f32 {
+        let score = self.score.unwrap_or(0.);
+        match player {
+            TicTacToePlayer::X => score,
+            TicTacToePlayer::O => -score
+        }
+    }
+}
+
+#[test]
+fn score_bounds_test() {
+    let game = ScoreTableState { row: None, column: None };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_score_bounds();
+    assert!(mcts.run_until(SearchLimit::iterations(10000)) < 10000);
+    assert_eq!(mcts.get_score_bounds(), (1., 1.));
+    assert_eq!(mcts.get_best_action(), Some(ScoreTableAction(1)));
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_score_bounds().with_expansion_strategy(ExpansionStrategy::All);
+    assert!(mcts.run_until(SearchLimit::iterations(10000)) < 10000);
+    assert_eq!(mcts.get_score_bounds(), (1., 1.));
+    assert_eq!(mcts.get_best_action(), Some(ScoreTableAction(1)));
+    let mut mcts = SearchTree::new(DetourState { is_detour: None, score: None }, UctPolicy::new(2.)).with_score_bounds();
+    mcts.run(6);
+    assert_eq!(mcts.get_best_action(), Some(ScoreTableAction(0)));
+}
+
+#[test]
+fn final_selection_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    for final_selection in [FinalSelection::MaxVisits, FinalSelection::MaxValue, FinalSelection::RobustMax, FinalSelection::SecureChild { confidence: 1. }, FinalSelection::Sample { temperature: 0.05 }] {
+        let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_seed(1).with_final_selection(final_selection);
+        mcts.run(2000);
+        assert_eq!(mcts.get_best_action().unwrap().pos, 0b100);
+    }
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_seed(1).with_final_selection(FinalSelection::Sample { temperature: 1. });
+    mcts.run(2000);
+    let mut sampled_actions = Vec::new();
+    for _ in 0..50 {
+        let action = mcts.get_best_action().unwrap();
+        if !sampled_actions.contains(&action) {
+            sampled_actions.push(action);
+        }
+    }
+    assert!(sampled_actions.len() > 1);
+}
+
+#[test]
+fn search_report_test() {
+    let game = TicTacToePosition {
+        board_x: 0b000000011,
+        board_o: 0b000011000,
+        turn: TicTacToePlayer::X
+    };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.));
+    mcts.run(2000);
+    let report = mcts.get_report(3);
+    assert_eq!(report.actions.len(), 5);
+    assert_eq!(report.actions[0].action, mcts.get_best_action().unwrap());
+    assert!(report.actions[0].mean_value > 0.9);
+    assert!(report.actions[0].confidence_interval.0 <= report.actions[0].mean_value && report.actions[0].mean_value <= report.actions[0].confidence_interval.1);
+    assert!(report.actions[1].confidence_interval.0 < report.actions[1].mean_value && report.actions[1].mean_value < report.actions[1].confidence_interval.1);
+    assert!((report.actions.iter().map(|action| action.visit_share).sum::<f32>() - 1.).abs() < 1e-4);
+    assert_eq!(report.principal_variation, vec![TicTacToeMove { pos: 0b100 }]);
+    let mut mcts = SearchTree::new(CorridorState { remaining: 3 }, UctPolicy::new(2.));
+    mcts.run(10);
+    assert_eq!(mcts.get_report(0).actions[0].confidence_interval, (1., 1.));
+    let mut mcts = SearchTree::new(ScoreTableState { row: None, column: None }, UctPolicy::new(2.)).with_seed(1);
+    mcts.run(100);
+    let report = mcts.get_report(0);
+    let spread_action = report.actions.iter().find(|action| action.action == ScoreTableAction(2)).unwrap();
+    assert!(spread_action.confidence_interval.1 - spread_action.mean_value > 1.96 / (spread_action.visits as f32).sqrt());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip_test() {
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_score_bounds();
+    mcts.run(2000);
+    let json = serde_json::to_string(&mcts).unwrap();
+    let mut loaded: SearchTree<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.to_string(), mcts.to_string());
+    assert_eq!(loaded.get_report(9), mcts.get_report(9));
+    assert_eq!(loaded.get_score_bounds(), mcts.get_score_bounds());
+    loaded.run(100);
+    assert_eq!(loaded.get_report(0).visits, mcts.get_report(0).visits + 100);
+    let oversized_json = json.replacen("\"child_count\":0", &format!("\"child_count\":{}", usize::MAX), 1);
+    assert!(serde_json::from_str::<SearchTree<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>>(&oversized_json).is_err());
+    let json = json.replacen(&format!("\"version\":{}", crate::SEARCH_TREE_FORMAT_VERSION), "\"version\":0", 1);
+    assert!(serde_json::from_str::<SearchTree<TicTacToePosition, TicTacToeMove, TicTacToePlayer, UctPolicy>>(&json).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_deep_tree_test() {
+    const DEPTH: u32 = 10_000;
+    let root = build_corridor_tree(DEPTH);
+    let json = serde_json::to_string(&root).unwrap();
+    let loaded: SearchNode<Step, Gambler> = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.get_node_count(), DEPTH + 1);
+    assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    assert!(serde_json::from_str::<SearchNode<Step, Gambler>>(&json.replacen("\"child_count\":1", "\"child_count\":2", 1)).is_err());
+}
+
+#[test]
+fn tree_export_test() {
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.));
+    mcts.run(1000);
+    let dot = mcts.to_dot(ExportOptions::default());
+    assert!(dot.starts_with("digraph SearchTree {") && dot.ends_with("}\n"));
+    assert_eq!(dot.matches(" -> ").count() as u32, mcts.get_node_count() - 1);
+    let dot = mcts.to_dot(ExportOptions::default().with_max_depth(1));
+    assert_eq!(dot.matches(" -> ").count(), 9);
+    let dot = mcts.to_dot(ExportOptions::default().with_min_visits(1001));
+    assert_eq!(dot.matches(" -> ").count(), 0);
+    let json = mcts.to_json(ExportOptions::default().with_max_depth(2).with_min_visits(10));
+    assert!(json.starts_with("{\"action\":null,\"state\":\"EXPANDED\",\"visits\":1000,"));
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+}
+
+#[test]
+fn tree_format_test() {
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.));
+    mcts.run(1000);
+    let full = mcts.display(FormatOptions::default()).to_string();
+    assert_eq!(format!("Node count: {}\n{}\n", mcts.get_node_count(), full), mcts.to_string());
+    let compact = mcts.display(FormatOptions::compact().with_max_depth(1).with_max_children(3).with_ucb(2.)).to_string();
+    let lines: Vec<&str> = compact.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1..].iter().all(|line| line.starts_with("|    [EXPANDED]") && line.contains(" mean=") && line.contains(" root_ucb=")));
+    let visits: Vec<u32> = lines[1..].iter().map(|line| line.split("visits=").nth(1).unwrap().split(' ').next().unwrap().parse().unwrap()).collect();
+    assert!(visits.windows(2).all(|pair| pair[0] >= pair[1]));
+    let filtered = mcts.display(FormatOptions::compact().with_min_visits(100)).to_string();
+    assert!(filtered.lines().count() < full.lines().count());
+}
+
+#[test]
+fn arena_search_tree_test() {
+    let game = TicTacToePosition { board_x: 0b000000011, board_o: 0b000011000, turn: TicTacToePlayer::X };
+    let mut mcts = ArenaSearchTree::new(game, UctPolicy::new(2.)).with_seed(1);
+    mcts.run(2000);
+    assert_eq!(mcts.get_best_action(), Some(TicTacToeMove { pos: 0b100 }));
+    let mut mcts = ArenaSearchTree::new(TicTacToePosition::new(), PuctPolicy::new(2.)).with_expansion_strategy(ExpansionStrategy::All);
+    mcts.run(5000);
+    let best = mcts.get_best_action().unwrap();
+    let node_count = mcts.get_node_count();
+    mcts.advance(&best);
+    let nodes = mcts.get_nodes();
+    assert!(nodes.len() > 1 && (nodes.len() as u32) < node_count);
+    assert_eq!(nodes[0].visits, nodes[nodes[0].first_child as usize..][..nodes[0].child_count as usize].iter().map(|n| n.visits).sum::<u32>() + 1);
+    let max_nodes = mcts.get_node_count() + 100;
+    mcts.run_until(SearchLimit::nodes(max_nodes));
+    assert!((max_nodes..max_nodes + 9).contains(&mcts.get_node_count()));
+    for (win_probability, expected_action) in [(0.2, GambleAction::Safe), (0.8, GambleAction::Gamble)] {
+        let game = GambleState { win_probability, is_gambling: false, reward: None };
+        let mut mcts = ArenaSearchTree::new(game, UctPolicy::new(0.5)).with_seed(1);
+        mcts.run(2000);
+        assert_eq!(mcts.get_best_action(), Some(expected_action));
+    }
+}
+
+/// A single-player game: step down a corridor of the given length for a reward of 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CorridorState {
+    remaining: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Step;
+
+impl GameAction for Step {}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Step")
+    }
+}
+
+impl GameState<Step, Gambler> for CorridorState {
+    fn get_actions(&self) -> Vec<Step> {
+        match self.remaining {
+            0 => Vec::new(),
+            _ => vec![Step]
+        }
+    }
+
+    fn apply_action(&mut self, _action: &Step) {
+        self.remaining -= 1;
+    }
+
+    fn get_turn(&self) -> Gambler {
+        Gambler
+    }
+
+    fn get_reward_for_player(&self, _player: Gambler) -> f32 {
+        1.
+    }
+}
+
+/// Builds a search tree of the corridor game with a chain of the given number of Step nodes below the root.
+fn build_corridor_tree(depth: u32) -> SearchNode<Step, Gambler> {
+    let mut node = SearchNode::new(Some(Step), Gambler);
+    for _ in 1..depth {
+        let mut parent = SearchNode::new(Some(Step), Gambler);
+        parent.state = NodeState::Expanded;
+        parent.children.push(node);
+        node = parent;
+    }
+    let mut root = SearchNode::new(None, Gambler);
+    root.state = NodeState::Expanded;
+    root.children.push(node);
+    root
+}
+
+#[test]
+fn deep_tree_test() {
+    const DEPTH: u32 = 100_000;
+    let mut root = build_corridor_tree(DEPTH - 1);
+    let context = SearchContext {
+        tree_policy: &UctPolicy::new(2.),
+        rollout_policy: &UniformRolloutPolicy,
+        prior_provider: &UniformPriorProvider,
+        config: &SearchConfig::default(),
+        players: &[],
+        can_expand: true
+    };
+    let mut rng = Rng::with_seed(0);
+    for _ in 0..3 {
+        root.run_iteration(&mut CorridorState { remaining: DEPTH }, &context, &mut rng);
+    }
+    assert_eq!(root.visits, 3);
+    assert_eq!(root.get_node_count(), DEPTH + 1);
+    let json = crate::tree_export::to_json(&root, ExportOptions::default());
+    assert_eq!(json.matches("\"action\":\"Step\"").count() as u32, DEPTH);
+    let display = TreeDisplay::new(&root, FormatOptions::compact().with_max_depth(2)).to_string();
+    assert_eq!(display.lines().count(), 3);
+}
+
+#[test]
+fn shared_deep_tree_test() {
+    const DEPTH: u32 = 100_000;
+    let root = SearchNode::from(SharedSearchNode::from(build_corridor_tree(DEPTH)));
+    assert_eq!(root.get_node_count(), DEPTH + 1);
+    drop(SharedSearchNode::from(root));
+}
+
+#[test]
+fn node_limit_test() {
+    let game = TicTacToePosition { board_x: 0b000000011, board_o: 0b000011000, turn: TicTacToePlayer::X };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(2.)).with_seed(1).with_node_limit(NodeLimit::prune(50));
+    mcts.run(5000);
+    let stats = mcts.get_node_limit_stats();
+    assert!(stats.prunes > 0 && stats.reclaimed_nodes > 0 && !stats.is_expansion_stopped);
+    assert!(mcts.get_node_count() < 50);
+    assert_eq!(mcts.to_dot(ExportOptions::default()).matches(" -> ").count() as u32, mcts.get_node_count() - 1);
+    assert_eq!(mcts.get_best_action(), Some(TicTacToeMove { pos: 0b100 }));
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_node_limit(NodeLimit::stop_expanding(300));
+    mcts.run(5000);
+    assert_eq!(mcts.get_node_count(), 300);
+    assert_eq!(mcts.get_report(0).visits, 5000);
+    assert!(mcts.get_node_limit_stats().is_expansion_stopped);
+    let best = mcts.get_best_action().unwrap();
+    mcts.advance(&best);
+    assert!(!mcts.get_node_limit_stats().is_expansion_stopped);
+    mcts.run(10);
+    assert_eq!(mcts.to_dot(ExportOptions::default()).matches(" -> ").count() as u32, mcts.get_node_count() - 1);
+    let mut mcts = SearchTree::new(TicTacToePosition::new(), UctPolicy::new(2.)).with_memory_limit(1);
+    mcts.run(100);
+    assert!(mcts.get_node_count() <= 2);
+    assert!(mcts.get_node_limit_stats().prunes > 0);
+}
+
+/// A single-player game: pick a number below 1000, or draw one at random, for a reward of the number over 1000.
+/// If ordered, the actions are scored by their number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NumberState {
+    is_random: bool,
+    is_ordered: bool,
+    number: Option<u32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Number(u32);
+
+impl GameAction for Number {}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl GameState<Number, Gambler> for NumberState {
+    fn get_actions(&self) -> Vec<Number> {
+        match self.number.is_none() && !self.is_random {
+            true => (0..1000).map(Number).collect(),
+            false => Vec::new()
+        }
+    }
+
+    fn apply_action(&mut self, action: &Number) {
+        self.number = Some(action.0);
+    }
+
+    fn get_turn(&self) -> Gambler {
+        Gambler
+    }
+
+    fn get_reward_for_player(&self, _player: Gambler) -> f32 {
+        self.number.unwrap_or(0) as f32 / 1000.
+    }
+
+    fn get_chance_outcomes(&self) -> Option<Vec<(Number, f32)>> {
+        match self.number.is_none() && self.is_random {
+            true => Some((0..1000).map(|number| (Number(number), 0.001)).collect()),
+            false => None
+        }
+    }
+
+    fn get_action_scores(&self) -> Option<Vec<(Number, f32)>> {
+        match self.is_ordered {
+            true => Some(self.get_actions().into_iter().map(|action| (action, action.0 as f32)).collect()),
+            false => None
+        }
+    }
+}
+
+#[test]
+fn progressive_widening_test() {
+    let game = NumberState { is_random: false, is_ordered: false, number: None };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.2)).with_seed(1);
+    mcts.run(400);
+    assert_eq!(mcts.get_report(0).actions.len(), 400);
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.2)).with_seed(1).with_progressive_widening(ProgressiveWidening::new(1., 0.5));
+    mcts.run(400);
+    let report = mcts.get_report(0);
+    assert!(report.actions.len() <= 20);
+    assert!(report.actions[0].visits > 100 && report.actions[0].action.0 > 800);
+    let game = NumberState { is_random: true, is_ordered: false, number: None };
+    let widening = ProgressiveWidening::new(2., 0.5);
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.2)).with_seed(1).with_double_progressive_widening(widening, widening);
+    mcts.run(400);
+    assert!(mcts.get_report(0).actions.len() <= 40);
+}
+
+#[test]
+fn action_ordering_test() {
+    let game = NumberState { is_random: false, is_ordered: true, number: None };
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.2)).with_seed(1);
+    mcts.run(10);
+    assert!(mcts.get_report(0).actions.iter().all(|action| action.action.0 >= 990));
+    let mut mcts = SearchTree::new(game, UctPolicy::new(0.2)).with_seed(1).with_progressive_widening(ProgressiveWidening::new(1., 0.5));
+    mcts.run(400);
+    assert!(mcts.get_report(0).actions.iter().all(|action| action.action.0 >= 980));
+    let mut mcts = ArenaSearchTree::new(game, UctPolicy::new(0.2)).with_seed(1);
+    mcts.run(10);
+    let nodes = mcts.get_nodes();
+    let children = &nodes[nodes[0].first_child as usize..][..nodes[0].child_count as usize];
+    assert!(children.iter().filter(|child| child.visits > 0).all(|child| child.action.unwrap().0 >= 990));
+}
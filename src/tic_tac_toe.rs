@@ -5,6 +5,7 @@ use std::fmt;
 use crate::{GameAction, GameState, Player};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TicTacToeMove {
     pub pos: u16
 }
@@ -30,6 +31,7 @@ impl Display for TicTacToeMove {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TicTacToePlayer {
     X,
     O
@@ -85,12 +87,19 @@ pub fn print_board(board: u16, c: char) {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TicTacToePosition {
     pub board_x: u16,
     pub board_o: u16,
     pub turn: TicTacToePlayer,
 }
 
+impl Default for TicTacToePosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TicTacToePosition {
     pub fn new() -> TicTacToePosition {
         TicTacToePosition {
@@ -192,7 +201,7 @@ impl GameState<TicTacToeMove, TicTacToePlayer> for TicTacToePosition {
     fn get_reward_for_player(&self, player: TicTacToePlayer) -> f32 {
         match self.get_winner() {
             Some(winner) => {
-                return if winner == player {
+                if winner == player {
                     1.
                 } else {
                     -1.
@@ -0,0 +1,94 @@
+//! Contains the TranspositionTable struct, which shares statistics between nodes that reach the same game state.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Represents a bounded table of statistics per game state, used by transposition search.
+/// Game states are identified by their hash, and compared for equality to rule out hash collisions.
+pub struct TranspositionTable<S> {
+    /// The entries of the table, by hash of their game state.
+    entries: HashMap<u64, TranspositionEntry<S>>,
+    /// The maximum number of entries. Once full, new game states are not tracked.
+    capacity: usize,
+    /// Returns the hash of a game state.
+    hash_state: fn(&S) -> u64,
+    /// Returns whether two game states are equal.
+    eq_state: fn(&S, &S) -> bool
+}
+
+/// Represents the statistics of a game state, shared by every node that reaches it.
+struct TranspositionEntry<S> {
+    /// The game state of this entry.
+    state: S,
+    /// The number of times the game state has been visited, through any path.
+    visits: u32,
+    /// The mean value of the game state, for the player whose turn it was at the root node.
+    value: f32
+}
+
+impl<S> TranspositionTable<S> where S: Clone {
+    /// Constructs a new empty table holding at most the given number of game states.
+    pub fn new(capacity: usize) -> Self where S: Hash + Eq {
+        assert!(capacity > 0, "Transposition table capacity must be positive");
+        TranspositionTable {
+            entries: HashMap::new(),
+            capacity,
+            hash_state: |state| {
+                let mut hasher = DefaultHasher::new();
+                state.hash(&mut hasher);
+                hasher.finish()
+            },
+            eq_state: |a, b| a == b
+        }
+    }
+
+    /// Returns the number of game states in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the table holds no game states.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of game states in the table.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes all game states from the table.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the key of the given game state, adding it to the table if needed.
+    /// Returns None if the game state can't be tracked, because the table is full or another state has the same hash.
+    pub(crate) fn find_or_insert(&mut self, state: &S) -> Option<u64> {
+        let key = (self.hash_state)(state);
+        match self.entries.get(&key) {
+            Some(entry) => match (self.eq_state)(&entry.state, state) {
+                true => Some(key),
+                false => None
+            },
+            None if self.entries.len() < self.capacity => {
+                self.entries.insert(key, TranspositionEntry { state: state.clone(), visits: 0, value: 0.0 });
+                Some(key)
+            },
+            None => None
+        }
+    }
+
+    /// Returns the visits and mean value of the game state with the given key, if it is in the table.
+    pub(crate) fn get(&self, key: u64) -> Option<(u32, f32)> {
+        self.entries.get(&key).map(|entry| (entry.visits, entry.value))
+    }
+
+    /// Sets the visits and mean value of the game state with the given key, if it is in the table.
+    pub(crate) fn set(&mut self, key: u64, visits: u32, value: f32) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.visits = visits;
+            entry.value = value;
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Contains the ExportOptions struct and the functions that export a search tree as Graphviz DOT or JSON.
+
+use std::cmp::Reverse;
+use std::fmt::Write;
+use crate::game::{GameAction, Player};
+use crate::search_node::{NodeState, SearchNode};
+
+/// Represents which nodes of a search tree are exported. The root node is always exported.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// The maximum depth of exported nodes, where the root node has depth 0. None exports every depth.
+    pub max_depth: Option<usize>,
+    /// The minimum number of visits of exported nodes. Nodes with fewer visits are left out, along with their subtrees.
+    pub min_visits: u32,
+    /// The maximum number of exported children of each node, which are then the most visited ones, sorted by visits.
+    /// None exports every child, in order.
+    pub max_children: Option<usize>
+}
+
+impl ExportOptions {
+    /// Adds a limit on the depth of exported nodes.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Adds a minimum number of visits of exported nodes.
+    pub fn with_min_visits(mut self, min_visits: u32) -> Self {
+        self.min_visits = min_visits;
+        self
+    }
+
+    /// Adds a limit on the number of exported children of each node, keeping the most visited ones.
+    pub fn with_max_children(mut self, max_children: usize) -> Self {
+        self.max_children = Some(max_children);
+        self
+    }
+
+    /// Returns the children of the given node, at the given depth, that should be exported.
+    pub(crate) fn exported_children<'a, A, Pl>(&self, node: &'a SearchNode<A, Pl>, depth: usize) -> Vec<&'a SearchNode<A, Pl>> where A: GameAction, Pl: Player {
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return Vec::new();
+        }
+        let mut children: Vec<_> = node.children.iter().filter(|child| child.visits >= self.min_visits).collect();
+        if let Some(max_children) = self.max_children {
+            children.sort_by_key(|child| Reverse(child.visits));
+            children.truncate(max_children);
+        }
+        children
+    }
+}
+
+/// Returns the name of the given node state, as used in exports.
+pub(crate) fn state_name(state: NodeState) -> &'static str {
+    match state {
+        NodeState::ExpandableLeaf => "EXPANDABLE_LEAF",
+        NodeState::TerminalLeaf => "TERMINAL_LEAF",
+        NodeState::Expanded => "EXPANDED",
+        NodeState::Chance => "CHANCE"
+    }
+}
+
+/// Returns the mean value of the given node, or zero if it hasn't been visited.
+fn mean_value<A, Pl>(node: &SearchNode<A, Pl>) -> f32 where A: GameAction, Pl: Player {
+    match node.visits {
+        0 => 0.0,
+        visits => node.total_value / visits as f32
+    }
+}
+
+/// Returns the given text as a quoted string, escaped for both DOT and JSON.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).expect("Expected writing to a string to succeed"),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the given number as a JSON value, which is null if it isn't finite.
+fn json_number(value: f32) -> String {
+    match value.is_finite() {
+        true => value.to_string(),
+        false => "null".to_string()
+    }
+}
+
+/// Exports the tree rooted at the given node as a Graphviz DOT digraph.
+/// Nodes are labelled with their action and statistics and coloured by state,
+/// and edges are drawn thicker the larger the share of the root's visits that went through them.
+pub(crate) fn to_dot<A, Pl>(root: &SearchNode<A, Pl>, options: ExportOptions) -> String where A: GameAction, Pl: Player {
+    let mut out = String::from("digraph SearchTree {\n    node [shape=box, style=filled];\n");
+    // The nodes left to export, with their depth and the ID of their parent, in reverse order.
+    let mut stack = vec![(root, 0, None)];
+    let mut next_id = 0;
+    while let Some((node, depth, parent_id)) = stack.pop() {
+        let node_id = next_id;
+        next_id += 1;
+        let action = match node.action {
+            Some(action) => format!("{:?}", action),
+            None => "ROOT".to_string()
+        };
+        let label = format!("{}\nvisits={} mean={:.3}", action, node.visits, mean_value(node));
+        let color = match node.state {
+            NodeState::ExpandableLeaf => "lightblue",
+            NodeState::TerminalLeaf => "lightgray",
+            NodeState::Expanded => "white",
+            NodeState::Chance => "khaki"
+        };
+        writeln!(out, "    n{} [label={}, fillcolor={}, tooltip={}];", node_id, quote(&label), color, quote(state_name(node.state))).expect("Expected writing to a string to succeed");
+        if let Some(parent_id) = parent_id {
+            let penwidth = 1.0 + 9.0 * node.visits as f32 / root.visits.max(1) as f32;
+            writeln!(out, "    n{} -> n{} [penwidth={:.2}, label=\"{}\"];", parent_id, node_id, penwidth, node.visits).expect("Expected writing to a string to succeed");
+        }
+        for child in options.exported_children(node, depth).into_iter().rev() {
+            stack.push((child, depth + 1, Some(node_id)));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Exports the tree rooted at the given node as JSON, where each node is an object
+/// with its action (formatted with Debug), state, visits, total and mean values, prior and children.
+pub(crate) fn to_json<A, Pl>(root: &SearchNode<A, Pl>, options: ExportOptions) -> String where A: GameAction, Pl: Player {
+    /// A step of the export: writing a node with its depth and whether it is the first of its siblings, or closing a node.
+    enum Step<'a, A, Pl> where A: GameAction, Pl: Player {
+        Open(&'a SearchNode<A, Pl>, usize, bool),
+        Close
+    }
+    let mut out = String::new();
+    // The steps left to take, in reverse order.
+    let mut stack = vec![Step::Open(root, 0, true)];
+    while let Some(step) = stack.pop() {
+        let (node, depth, is_first) = match step {
+            Step::Open(node, depth, is_first) => (node, depth, is_first),
+            Step::Close => {
+                out.push_str("]}");
+                continue;
+            }
+        };
+        if !is_first {
+            out.push(',');
+        }
+        let action = match node.action {
+            Some(action) => quote(&format!("{:?}", action)),
+            None => "null".to_string()
+        };
+        write!(out, "{{\"action\":{},\"state\":{},\"visits\":{},\"total_value\":{},\"mean_value\":{},\"prior\":{},\"children\":[",
+               action, quote(state_name(node.state)), node.visits, json_number(node.total_value), json_number(mean_value(node)), json_number(node.prior))
+            .expect("Expected writing to a string to succeed");
+        stack.push(Step::Close);
+        for (index, child) in options.exported_children(node, depth).into_iter().enumerate().rev() {
+            stack.push(Step::Open(child, depth + 1, index == 0));
+        }
+    }
+    out
+}
@@ -0,0 +1,132 @@
+//! Contains the FormatOptions and TreeDisplay structs, which pretty-print a configurable part of a search tree.
+
+use std::fmt;
+use crate::game::{GameAction, Player};
+use crate::search_node::SearchNode;
+use crate::tree_export::{state_name, ExportOptions};
+
+/// Represents how a search tree is pretty-printed.
+/// The default prints every node with its multi-line action, as the `Display` implementations do.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FormatOptions {
+    /// Which nodes are printed.
+    pub nodes: ExportOptions,
+    /// Whether to print the `Display` output of each node's action below it.
+    pub show_action: bool,
+    /// Whether to print the mean value of each node.
+    pub show_mean_value: bool,
+    /// The exploration constant with which to print the root player's upper confidence bound on the value of each node, if any.
+    pub ucb_exploration_constant: Option<f32>
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            nodes: ExportOptions::default(),
+            show_action: true,
+            show_mean_value: false,
+            ucb_exploration_constant: None
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Constructs options that print a compact summary: one line per node, with its mean value.
+    pub fn compact() -> Self {
+        Self::default().with_show_action(false).with_show_mean_value(true)
+    }
+
+    /// Limits the depth of printed nodes.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.nodes = self.nodes.with_max_depth(max_depth);
+        self
+    }
+
+    /// Leaves out the nodes with fewer than the given number of visits.
+    pub fn with_min_visits(mut self, min_visits: u32) -> Self {
+        self.nodes = self.nodes.with_min_visits(min_visits);
+        self
+    }
+
+    /// Only prints the given number of most visited children of each node, sorted by visits.
+    pub fn with_max_children(mut self, max_children: usize) -> Self {
+        self.nodes = self.nodes.with_max_children(max_children);
+        self
+    }
+
+    /// Sets whether to print the `Display` output of each node's action.
+    pub fn with_show_action(mut self, show_action: bool) -> Self {
+        self.show_action = show_action;
+        self
+    }
+
+    /// Sets whether to print the mean value of each node.
+    pub fn with_show_mean_value(mut self, show_mean_value: bool) -> Self {
+        self.show_mean_value = show_mean_value;
+        self
+    }
+
+    /// Prints the upper confidence bound on the value of each node for the root player, with the given exploration constant.
+    /// This is the UCB value that UCT maximizes when the root player is to move; other players minimize the lower bound instead.
+    /// In max^n search, values are already for the player who chose each node, so the bound is theirs.
+    pub fn with_ucb(mut self, exploration_constant: f32) -> Self {
+        self.ucb_exploration_constant = Some(exploration_constant);
+        self
+    }
+}
+
+/// Pretty-prints the subtree of a search node with the given options, when formatted with `Display`.
+pub struct TreeDisplay<'a, A, Pl> where A: GameAction, Pl: Player {
+    /// The root node of the printed subtree.
+    node: &'a SearchNode<A, Pl>,
+    /// How the subtree is printed.
+    options: FormatOptions
+}
+
+impl<'a, A, Pl> TreeDisplay<'a, A, Pl> where A: GameAction, Pl: Player {
+    /// Constructs a display of the subtree of the given node, printed with the given options.
+    pub fn new(node: &'a SearchNode<A, Pl>, options: FormatOptions) -> Self {
+        TreeDisplay { node, options }
+    }
+}
+
+impl<A, Pl> fmt::Display for TreeDisplay<'_, A, Pl> where A: GameAction, Pl: Player {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = &self.options;
+        // The nodes left to print, with the visits of their parent and their indent level, in reverse order.
+        let mut stack = vec![(self.node, self.node.visits, 0)];
+        while let Some((node, parent_visits, indent_level)) = stack.pop() {
+            for _ in 0..indent_level {
+                f.write_str("|    ")?;
+            }
+            match node.action {
+                Some(a) => {
+                    write!(f, "[{}] {:?} total={} visits={}", state_name(node.state), a, node.total_value, node.visits)?;
+                },
+                None => write!(f, "[ROOT] total={} visits={}", node.total_value, node.visits)?
+            }
+            let mean_value = node.total_value / node.visits as f32;
+            if options.show_mean_value && node.visits > 0 {
+                write!(f, " mean={:.3}", mean_value)?;
+            }
+            if let Some(exploration_constant) = options.ucb_exploration_constant {
+                if node.action.is_some() && node.visits > 0 {
+                    write!(f, " root_ucb={:.3}", mean_value + exploration_constant*((parent_visits as f32).ln()/node.visits as f32).sqrt())?;
+                }
+            }
+            writeln!(f)?;
+            if let (Some(a), true) = (node.action, options.show_action) {
+                for line in format!("{}", a).split('\n') {
+                    for _ in 0..(indent_level + 1) {
+                        f.write_str("|    ")?;
+                    }
+                    writeln!(f, "{}", line)?;
+                }
+            }
+            for child in options.nodes.exported_children(node, indent_level).into_iter().rev() {
+                stack.push((child, node.visits, indent_level + 1));
+            }
+        }
+        Ok(())
+    }
+}
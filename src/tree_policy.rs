@@ -1,15 +1,47 @@
-//! Contains the trait that defines a tree policy, as well as a preset implementation (UctPolicy).
+//! Contains the trait that defines a tree policy, as well as preset implementations (UctPolicy and PuctPolicy).
 
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use crate::arena_search_tree::ArenaNode;
 use crate::game::{GameAction, Player};
 use crate::search_node::SearchNode;
+use crate::shared_search_node::SharedSearchNode;
 
 /// A trait that defines a tree policy.
 pub trait TreePolicy<A, Pl>: Sized where A: GameAction, Pl: Player {
     /// Selects a child node to explore.
     fn select_child<'a>(&self, node: &'a mut SearchNode<A, Pl>, is_max_player_turn: bool) -> &'a mut SearchNode<A, Pl>;
+
+    /// Selects a child node to explore among those whose action is one of the available actions.
+    /// Used by information set search, where the legal actions depend on the determinization,
+    /// and where each child's `availability` counts how often it could have been selected.
+    /// The default implementation hides the other children from `select_child`.
+    fn select_available_child<'a>(&self, node: &'a mut SearchNode<A, Pl>, available_actions: &[A], is_max_player_turn: bool) -> &'a mut SearchNode<A, Pl> {
+        let (available, unavailable): (Vec<_>, Vec<_>) = std::mem::take(&mut node.children).into_iter()
+            .partition(|child| available_actions.contains(&child.action.expect("Child node without action")));
+        node.children = available;
+        let action = self.select_child(node, is_max_player_turn).action;
+        node.children.extend(unavailable);
+        node.children.iter_mut().find(|child| child.action == action).expect("No best child found")
+    }
+}
+
+/// A trait that defines a tree policy for a search tree shared between threads.
+pub trait SharedTreePolicy<A, Pl>: Sync where A: GameAction, Pl: Player {
+    /// Selects a child node to explore, given the number of visits to the parent node (including pending ones).
+    /// Each child's `virtual_visits` counts the threads currently descending through it;
+    /// every such pending visit should be treated as a loss of `virtual_loss` for the player to move.
+    fn select_shared_child<'a>(&self, parent_visits: u32, children: &'a [Arc<SharedSearchNode<A, Pl>>], is_root_player_turn: bool, virtual_loss: f32) -> &'a Arc<SharedSearchNode<A, Pl>>;
+}
+
+/// A trait that defines a tree policy for an arena search tree, whose children are stored contiguously.
+pub trait ArenaTreePolicy<A, Pl> where A: GameAction, Pl: Player {
+    /// Selects a child node to explore, given the number of visits to the parent node, and returns its index in `children`.
+    fn select_arena_child(&self, parent_visits: u32, children: &[ArenaNode<A>], is_root_player_turn: bool) -> usize;
 }
 
 /// A preset implementation of a tree policy, using the UCT formula.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UctPolicy {
     /// The exploration constant to use.
     pub exploration_constant: f32
@@ -19,7 +51,7 @@ impl UctPolicy {
     pub fn new(exploration_constant: f32) -> Self {
         assert!(exploration_constant > 0.0, "Exploration constant must be positive");
         Self {
-            exploration_constant: exploration_constant
+            exploration_constant
         }
     }
 }
@@ -61,4 +93,147 @@ impl<A, Pl> TreePolicy<A, Pl> for UctPolicy where A: GameAction, Pl: Player {
             }
         }
     }
-}
\ No newline at end of file
+
+    fn select_available_child<'a>(&self, node: &'a mut SearchNode<A, Pl>, available_actions: &[A], is_root_player_turn: bool) -> &'a mut SearchNode<A, Pl> {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_ucb: f32 = f32::NEG_INFINITY;
+        let mut best_child : Option<&'a mut SearchNode<A, Pl>> = None;
+        for child in node.children.iter_mut() {
+            if !available_actions.contains(&child.action.expect("Child node without action")) {
+                continue;
+            }
+            if child.visits == 0 {
+                return child;
+            }
+            let child_ucb = sign*child.total_value / child.visits as f32 + self.exploration_constant*((child.availability as f32).ln()/child.visits as f32).sqrt();
+            if child_ucb > highest_ucb {
+                highest_ucb = child_ucb;
+                best_child = Some(child);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}
+
+impl<A, Pl> SharedTreePolicy<A, Pl> for UctPolicy where A: GameAction, Pl: Player {
+    fn select_shared_child<'a>(&self, parent_visits: u32, children: &'a [Arc<SharedSearchNode<A, Pl>>], is_root_player_turn: bool, virtual_loss: f32) -> &'a Arc<SharedSearchNode<A, Pl>> {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_ucb: f32 = f32::NEG_INFINITY;
+        let mut best_child: Option<&'a Arc<SharedSearchNode<A, Pl>>> = None;
+        let parent_visits_ln = (parent_visits as f32).ln();
+        for child in children {
+            let virtual_visits = child.virtual_visits.load(Ordering::Relaxed);
+            let visits = child.visits.load(Ordering::Relaxed) + virtual_visits;
+            if visits == 0 {
+                return child;
+            }
+            let value = sign*child.total_value() - virtual_loss*virtual_visits as f32;
+            let child_ucb = value / visits as f32 + self.exploration_constant*(parent_visits_ln/visits as f32).sqrt();
+            if child_ucb > highest_ucb {
+                highest_ucb = child_ucb;
+                best_child = Some(child);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}
+
+impl<A, Pl> ArenaTreePolicy<A, Pl> for UctPolicy where A: GameAction, Pl: Player {
+    fn select_arena_child(&self, parent_visits: u32, children: &[ArenaNode<A>], is_root_player_turn: bool) -> usize {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_ucb: f32 = f32::NEG_INFINITY;
+        let mut best_child: Option<usize> = None;
+        let parent_visits_ln = (parent_visits as f32).ln();
+        for (index, child) in children.iter().enumerate() {
+            if child.visits == 0 {
+                return index;
+            }
+            let child_ucb = sign*child.total_value / child.visits as f32 + self.exploration_constant*(parent_visits_ln/child.visits as f32).sqrt();
+            if child_ucb > highest_ucb {
+                highest_ucb = child_ucb;
+                best_child = Some(index);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}
+
+/// A preset implementation of a tree policy, using the PUCT formula from AlphaZero.
+/// Exploration is weighted by each child's `prior`, as set by the search tree's prior provider.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PuctPolicy {
+    /// The exploration constant to use.
+    pub exploration_constant: f32
+}
+
+impl PuctPolicy {
+    pub fn new(exploration_constant: f32) -> Self {
+        assert!(exploration_constant > 0.0, "Exploration constant must be positive");
+        Self {
+            exploration_constant
+        }
+    }
+
+    /// Returns the PUCT score of a child, from the perspective of the player to move. Unvisited children have a value of zero.
+    fn score(&self, sign: f32, value: f32, visits: u32, prior: f32, parent_visits_sqrt: f32) -> f32 {
+        let mean_value = match visits {
+            0 => 0.0,
+            _ => sign*value / visits as f32
+        };
+        mean_value + self.exploration_constant*prior*parent_visits_sqrt/(1 + visits) as f32
+    }
+}
+
+impl<A, Pl> TreePolicy<A, Pl> for PuctPolicy where A: GameAction, Pl: Player {
+    fn select_child<'a>(&self, node: &'a mut SearchNode<A, Pl>, is_root_player_turn: bool) -> &'a mut SearchNode<A, Pl> {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_score: f32 = f32::NEG_INFINITY;
+        let mut best_child : Option<&'a mut SearchNode<A, Pl>> = None;
+        let parent_visits_sqrt = (node.visits as f32).sqrt();
+        for child in node.children.iter_mut() {
+            let child_score = self.score(sign, child.total_value, child.visits, child.prior, parent_visits_sqrt);
+            if child_score > highest_score {
+                highest_score = child_score;
+                best_child = Some(child);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}
+
+impl<A, Pl> SharedTreePolicy<A, Pl> for PuctPolicy where A: GameAction, Pl: Player {
+    fn select_shared_child<'a>(&self, parent_visits: u32, children: &'a [Arc<SharedSearchNode<A, Pl>>], is_root_player_turn: bool, virtual_loss: f32) -> &'a Arc<SharedSearchNode<A, Pl>> {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_score: f32 = f32::NEG_INFINITY;
+        let mut best_child: Option<&'a Arc<SharedSearchNode<A, Pl>>> = None;
+        let parent_visits_sqrt = (parent_visits as f32).sqrt();
+        for child in children {
+            let virtual_visits = child.virtual_visits.load(Ordering::Relaxed);
+            let visits = child.visits.load(Ordering::Relaxed) + virtual_visits;
+            let value = child.total_value() - sign*virtual_loss*virtual_visits as f32;
+            let child_score = self.score(sign, value, visits, child.prior, parent_visits_sqrt);
+            if child_score > highest_score {
+                highest_score = child_score;
+                best_child = Some(child);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}
+
+impl<A, Pl> ArenaTreePolicy<A, Pl> for PuctPolicy where A: GameAction, Pl: Player {
+    fn select_arena_child(&self, parent_visits: u32, children: &[ArenaNode<A>], is_root_player_turn: bool) -> usize {
+        let sign = if is_root_player_turn { 1.0 } else { -1.0 };
+        let mut highest_score: f32 = f32::NEG_INFINITY;
+        let mut best_child: Option<usize> = None;
+        let parent_visits_sqrt = (parent_visits as f32).sqrt();
+        for (index, child) in children.iter().enumerate() {
+            let child_score = self.score(sign, child.total_value, child.visits, child.prior, parent_visits_sqrt);
+            if child_score > highest_score {
+                highest_score = child_score;
+                best_child = Some(index);
+            }
+        }
+        best_child.expect("No best child found")
+    }
+}